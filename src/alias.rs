@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use crate::command_detection::{executable_name, split_simple_command};
+use crate::config::Config;
+
+/// Names taken by the subcommands of `hyprsession command`, which can't be used for commands
+pub const RESERVED_NAMES: &[&str] = &["list", "show", "remove", "migrate"];
+
+/// The command aliased to a window's initial class, matched ignoring case
+pub fn class_alias(config: &Config, initial_class: &str) -> Option<Vec<String>> {
+    config.aliases
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(initial_class))
+        .map(|(_, argv)| argv.clone())
+        .filter(|argv| !argv.is_empty())
+}
+
+/// Replace the program of a detected command with the command aliased to its executable name, keeping its arguments
+pub fn apply_alias(argv: Vec<String>, config: &Config) -> Vec<String> {
+    let alias = argv.first().and_then(|program| config.aliases.get(executable_name(program)));
+    match alias.filter(|alias| !alias.is_empty()) {
+        Some(alias) => alias.iter().chain(argv.iter().skip(1)).cloned().collect(),
+        None => argv,
+    }
+}
+
+/// The command of a bridge script written by earlier versions, which hold a shebang and a
+/// single command line without a trailing newline. Lines the shell would do more with than
+/// run a program, such as ones with expansions, operators or variable assignments, are left alone.
+pub fn script_command(content: &str) -> Option<Vec<String>> {
+    let line = content.strip_prefix("#!/bin/sh\n")?;
+    let argv = split_simple_command(line)?;
+    let expands = |arg: &String| arg.starts_with('~');
+    (!argv[0].contains('=') && !argv.iter().any(expands)).then_some(argv)
+}
+
+/// Bridge scripts in a directory with the commands they run, sorted by name
+pub fn bridge_scripts(dir: &Path) -> Vec<(String, Vec<String>)> {
+    let mut scripts: Vec<(String, Vec<String>)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter_map(|entry| {
+            let argv = script_command(&fs::read_to_string(entry.path()).ok()?)?;
+            Some((entry.file_name().to_string_lossy().into_owned(), argv))
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell;
+
+    fn argv(line: &str) -> Vec<String> {
+        shell::split(line).unwrap()
+    }
+
+    #[test]
+    fn test_aliases() {
+        let config: Config = serde_json::from_str(r#"{
+            "aliases": {
+                "firefox": ["flatpak", "run", "org.mozilla.firefox"],
+                "Spotify": ["spotify-launcher"],
+                "empty": []
+            }
+        }"#).unwrap();
+
+        assert_eq!(class_alias(&config, "spotify").unwrap(), argv("spotify-launcher"));
+        assert!(class_alias(&config, "empty").is_none());
+        assert!(class_alias(&config, "kitty").is_none());
+
+        let aliased = apply_alias(argv("/usr/bin/firefox --new-window"), &config);
+        assert_eq!(aliased, argv("flatpak run org.mozilla.firefox --new-window"));
+        assert_eq!(apply_alias(argv("kitty -e htop"), &config), argv("kitty -e htop"));
+    }
+
+    #[test]
+    fn test_bridge_scripts() {
+        assert_eq!(script_command("#!/bin/sh\nflatpak run 'org.app'").unwrap(), argv("flatpak run org.app"));
+        assert!(script_command("#!/bin/sh\nflatpak run org.app\n").is_none());
+        assert!(script_command("#!/bin/bash\nflatpak run org.app").is_none());
+        assert!(script_command("#!/bin/sh\n").is_none());
+        assert!(script_command("#!/bin/sh\ncd ~/app && ./run").is_none());
+        assert!(script_command("#!/bin/sh\nFOO=$BAR app").is_none());
+        assert!(script_command("#!/bin/sh\nFOO=bar app").is_none());
+        assert!(script_command("#!/bin/sh\napp ~/notes.txt").is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("firefox"), "#!/bin/sh\nflatpak run org.mozilla.firefox").unwrap();
+        fs::write(dir.path().join("backup"), "#!/bin/sh\nset -e\nrsync -a ~ /mnt\n").unwrap();
+        fs::write(dir.path().join("binary"), [0x7f, b'E', b'L', b'F']).unwrap();
+        fs::write(dir.path().join("run-app"), "#!/bin/sh\ncd ~/app && ./run").unwrap();
+
+        let scripts = bridge_scripts(dir.path());
+        assert_eq!(scripts, vec![(String::from("firefox"), argv("flatpak run org.mozilla.firefox"))]);
+        assert!(bridge_scripts(&dir.path().join("missing")).is_empty());
+        assert!(dir.path().join("run-app").exists());
+    }
+}
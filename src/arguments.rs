@@ -0,0 +1,208 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::command_detection::executable_name;
+
+/// Chromium based browsers and Electron, which pass their process type to the processes they start
+const CHROMIUM_PROGRAMS: &[&str] = &[
+    "chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "brave", "brave-browser",
+    "vivaldi", "vivaldi-stable", "microsoft-edge", "microsoft-edge-stable", "electron", "code", "code-oss", "codium",
+];
+
+/// Arguments that only mean something to the run of a program they were captured from, as
+/// (executables, pattern) pairs where no executables apply to every program
+const BUILTIN_FILTERS: &[(&[&str], &str)] = &[
+    (&[], r"^--gapplication-service$"),
+    (CHROMIUM_PROGRAMS, r"^--type=.*$"),
+    (&[], r"^--[\w-]*fd=\d+$"),
+    (&[], r"^(/tmp|/run/user/\d+|/dev/fd|/proc/self/fd)/"),
+    (&[], r"^--[\w-]+=(/tmp|/run/user/\d+)/"),
+    (&[], r"(?i)^--(\w+-)*(token|secret|password|passwd|auth)(-\w+)*=.*$"),
+    (&["soffice.bin"], r"^--splash-pipe=\d+$"),
+];
+
+/// Options of programs that take the next argument as their value, dropped when their value is
+const VALUE_OPTIONS: &[(&[&str], &[&str])] = &[
+    (&["kitty"], &["-d", "--directory", "-o", "--override", "-c", "--config", "--listen-on", "--session"]),
+    (&["alacritty"], &["--working-directory", "--config-file", "--socket"]),
+    (&["foot", "footclient"], &["-D", "--working-directory", "-c", "--config", "-s", "--server-socket"]),
+    (&["wezterm", "wezterm-gui"], &["--cwd", "--config-file"]),
+    (&["firefox", "firefox-esr", "librewolf", "floorp", "zen", "zen-browser", "waterfox"], &["-profile", "--profile"]),
+];
+
+/// Drops or rewrites arguments matching a regular expression before a command is saved
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArgumentFilter {
+    /// Name of the executable the filter applies to, or every program if missing
+    #[serde(default)]
+    pub executable: Option<String>,
+    /// Regular expression matched against each argument
+    pub pattern: String,
+    /// Replacement for the matched part of the argument, which is dropped if missing.
+    /// `$1` and `${name}` refer to capture groups.
+    #[serde(default)]
+    pub replace: Option<String>,
+    /// Whether a dropped argument is the value of the option before it, which is dropped too
+    #[serde(default)]
+    pub drop_option: bool,
+}
+
+/// An argument filter with its pattern compiled
+#[derive(Clone, Debug)]
+pub struct CompiledFilter {
+    /// Executables the filter applies to, or every program if empty
+    executables: Vec<String>,
+    regex: Regex,
+    replace: Option<String>,
+    drop_option: bool,
+}
+
+impl CompiledFilter {
+    /// Whether the filter drops an argument given as the value of the option before it
+    fn drops_option(&self, arg: &str) -> bool {
+        self.drop_option && self.replace.is_none() && self.regex.is_match(arg)
+    }
+}
+
+/// Compile the user's filters followed by the built-in ones, skipping invalid patterns
+pub fn compile(filters: &[ArgumentFilter]) -> Vec<CompiledFilter> {
+    let user = filters.iter().filter_map(|filter| match Regex::new(&filter.pattern) {
+        Ok(regex) => Some(CompiledFilter {
+            executables: filter.executable.iter().cloned().collect(),
+            regex,
+            replace: filter.replace.clone(),
+            drop_option: filter.drop_option,
+        }),
+        Err(e) => {
+            eprintln!("Warning: Ignoring invalid argument filter '{}': {}", filter.pattern, e);
+            None
+        }
+    });
+    let builtin = BUILTIN_FILTERS.iter().map(|(executables, pattern)| CompiledFilter {
+        executables: executables.iter().map(|executable| executable.to_string()).collect(),
+        regex: Regex::new(pattern).expect("Invalid built-in argument filter"),
+        replace: None,
+        drop_option: false,
+    });
+    user.chain(builtin).collect()
+}
+
+/// Run an argument through the filters, returning None if it is dropped
+fn apply(filters: &[&CompiledFilter], arg: &str) -> Option<String> {
+    let mut arg = Some(arg.to_string());
+    for filter in filters.iter() {
+        arg = match arg {
+            Some(value) if filter.regex.is_match(&value) => filter.replace
+                .as_ref()
+                .map(|replace| filter.regex.replace_all(&value, replace.as_str()).into_owned())
+                .filter(|value| !value.is_empty()),
+            other => other,
+        };
+    }
+    arg
+}
+
+/// Whether an option of a program is known to take the next argument as its value
+fn takes_value(name: &str, option: &str) -> bool {
+    VALUE_OPTIONS
+        .iter()
+        .any(|(executables, options)| executables.contains(&name) && options.contains(&option))
+}
+
+/// Apply the filters for the program to each of its arguments, leaving the program itself untouched.
+/// Options lose their value with it when given as `--option=value`, and when given as `--option value`
+/// if the option is known to take a value or the filter says so.
+pub fn filter_arguments(argv: Vec<String>, filters: &[CompiledFilter]) -> Vec<String> {
+    let Some(program) = argv.first() else {
+        return argv;
+    };
+    let name = executable_name(program).to_string();
+    let filters: Vec<&CompiledFilter> = filters
+        .iter()
+        .filter(|filter| filter.executables.is_empty() || filter.executables.contains(&name))
+        .collect();
+
+    let mut args = argv.into_iter();
+    let mut filtered: Vec<String> = args.next().into_iter().collect();
+    for arg in args {
+        let value_dropped = arg
+            .split_once('=')
+            .filter(|(option, _)| option.starts_with('-'))
+            .is_some_and(|(_, value)| apply(&filters, value).is_none());
+        match apply(&filters, &arg).filter(|_| !value_dropped) {
+            Some(arg) => filtered.push(arg),
+            None if !arg.starts_with('-') && filtered.len() > 1 => {
+                let option = &filtered[filtered.len() - 1];
+                let is_option = option.starts_with('-') && option != "--" && !option.contains('=');
+                if is_option && (takes_value(&name, option) || filters.iter().any(|filter| filter.drops_option(&arg))) {
+                    filtered.pop();
+                }
+            }
+            None => {}
+        }
+    }
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_builtin_filters() {
+        let builtin = compile(&[]);
+        let filtered = filter_arguments(argv(&[
+            "/usr/bin/nautilus", "--gapplication-service", "--fd=12", "--sync-fd=3", "/tmp/.mount_x/file",
+            "--socket=/run/user/1000/app.sock", "--auth-token=abc", "--new-window", "/home/user/tmp/notes.txt",
+        ]), &builtin);
+        assert_eq!(filtered, argv(&["/usr/bin/nautilus", "--new-window", "/home/user/tmp/notes.txt"]));
+
+        let office = argv(&["soffice.bin", "--splash-pipe=5", "report.odt"]);
+        assert_eq!(filter_arguments(office, &builtin), argv(&["soffice.bin", "report.odt"]));
+        let other = argv(&["mpv", "--splash-pipe=5"]);
+        assert_eq!(filter_arguments(other.clone(), &builtin), other);
+
+        // Options whose value is dropped go with it
+        let kitty = argv(&["kitty", "--directory", "/tmp/x", "--hold", "-o", "/run/user/1000/y", "htop"]);
+        assert_eq!(filter_arguments(kitty, &builtin), argv(&["kitty", "--hold", "htop"]));
+        let firefox = argv(&["firefox", "--profile=/tmp/scratch", "--new-window"]);
+        assert_eq!(filter_arguments(firefox, &builtin), argv(&["firefox", "--new-window"]));
+
+        let credentials = argv(&["app", "--author=me", "--api-token=x", "--password=y", "--auth=z", "--authority=w"]);
+        assert_eq!(filter_arguments(credentials, &builtin), argv(&["app", "--author=me", "--authority=w"]));
+
+        // Flags before a dropped argument stay when they aren't known to take a value
+        let mpv = argv(&["mpv", "--fs", "/tmp/clip.mp4"]);
+        assert_eq!(filter_arguments(mpv, &builtin), argv(&["mpv", "--fs"]));
+        let firefox = argv(&["firefox", "--new-window", "/tmp/page.html"]);
+        assert_eq!(filter_arguments(firefox, &builtin), argv(&["firefox", "--new-window"]));
+
+        // Process types are only dropped for Chromium and Electron
+        let chromium = argv(&["chromium", "--type=renderer", "--app=https://example.org"]);
+        assert_eq!(filter_arguments(chromium, &builtin), argv(&["chromium", "--app=https://example.org"]));
+        let other = argv(&["mkfs-gui", "--type=ext4"]);
+        assert_eq!(filter_arguments(other.clone(), &builtin), other);
+    }
+
+    #[test]
+    fn test_user_filters() {
+        let filters: Vec<ArgumentFilter> = serde_json::from_str(r#"[
+            { "executable": "code", "pattern": "^--folder-uri=vscode-remote://(.*)$", "replace": "--remote=$1" },
+            { "pattern": "^--verbose$" },
+            { "executable": "app", "pattern": "^\\d+$", "drop_option": true },
+            { "pattern": "(" }
+        ]"#).unwrap();
+
+        let filters = compile(&filters);
+        let filtered = filter_arguments(argv(&["code", "--folder-uri=vscode-remote://ssh/src", "--verbose"]), &filters);
+        assert_eq!(filtered, argv(&["code", "--remote=ssh/src"]));
+        let other = filter_arguments(argv(&["kitty", "--folder-uri=vscode-remote://x", "--verbose"]), &filters);
+        assert_eq!(other, argv(&["kitty", "--folder-uri=vscode-remote://x"]));
+
+        let app = filter_arguments(argv(&["app", "--fd", "3", "--fullscreen", "--port", "8080", "-x"]), &filters);
+        assert_eq!(app, argv(&["app", "--fullscreen", "-x"]));
+    }
+}
@@ -0,0 +1,258 @@
+use hyprland::data::Client;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use crate::command_detection::executable_name;
+use crate::process;
+
+/// Browsers sharing Firefox's profile handling, with the directory holding their `profiles.ini` relative to the home directory
+const FIREFOX_BROWSERS: &[(&str, &str)] = &[
+    ("firefox", ".mozilla/firefox"), ("firefox-esr", ".mozilla/firefox"), ("librewolf", ".librewolf"),
+    ("floorp", ".floorp"), ("zen", ".zen"), ("zen-browser", ".zen"), ("waterfox", ".waterfox"),
+];
+
+/// Chromium based browsers with their default user data directory relative to the home directory
+const CHROMIUM_BROWSERS: &[(&str, &str)] = &[
+    ("chromium", ".config/chromium"), ("chromium-browser", ".config/chromium"),
+    ("google-chrome", ".config/google-chrome"), ("google-chrome-stable", ".config/google-chrome"),
+    ("brave", ".config/BraveSoftware/Brave-Browser"), ("brave-browser", ".config/BraveSoftware/Brave-Browser"),
+    ("vivaldi", ".config/vivaldi"), ("vivaldi-stable", ".config/vivaldi"),
+    ("microsoft-edge", ".config/microsoft-edge"), ("microsoft-edge-stable", ".config/microsoft-edge"),
+];
+
+const FIREFOX_PROFILE_ARGS: &[&str] = &["-P", "--P", "-p", "-profile", "--profile"];
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_default())
+}
+
+/// Profiles listed in a `profiles.ini` file
+#[derive(Debug, Default, PartialEq)]
+struct FirefoxProfiles {
+    /// Profile used when none is given
+    default: Option<PathBuf>,
+    /// Names and directories of the profiles
+    profiles: Vec<(String, PathBuf)>,
+}
+
+/// Parse a `profiles.ini` file with relative paths resolved against the directory it is in
+fn parse_profiles_ini(content: &str, dir: &Path) -> FirefoxProfiles {
+    let mut result = FirefoxProfiles::default();
+    let mut install_default = None;
+    let mut profile_default = None;
+
+    // Sections are collected as (header, keys) so relative paths can be resolved once a section ends
+    let mut sections: Vec<(String, Vec<(String, String)>)> = vec![];
+    for line in content.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            sections.push((header.to_string(), vec![]));
+        } else if let (Some((key, value)), Some((_, keys))) = (line.split_once('='), sections.last_mut()) {
+            keys.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    for (header, keys) in sections {
+        let get = |name: &str| keys.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+        let Some(path) = get("Path").or_else(|| get("Default").filter(|_| header.starts_with("Install"))) else {
+            continue;
+        };
+        let path = if get("IsRelative") == Some("0") || Path::new(path).is_absolute() {
+            PathBuf::from(path)
+        } else {
+            dir.join(path)
+        };
+
+        if header.starts_with("Install") {
+            install_default.get_or_insert(path);
+        } else if header.starts_with("Profile") {
+            if get("Default") == Some("1") {
+                profile_default.get_or_insert(path.clone());
+            }
+            if let Some(name) = get("Name") {
+                result.profiles.push((name.to_string(), path));
+            }
+        }
+    }
+
+    // Newer versions keep a default per installation which takes precedence
+    result.default = install_default.or(profile_default);
+    result
+}
+
+/// The profile directory of a running Firefox, from the variable it sets when restarting or the lock it holds open
+fn firefox_profile_dir(pid: i32) -> Option<PathBuf> {
+    process::environ(pid)
+        .remove("XRE_PROFILE_PATH")
+        .map(PathBuf::from)
+        .or_else(|| {
+            process::open_files(pid)
+                .into_iter()
+                .find(|path| path.file_name().is_some_and(|name| name == ".parentlock"))
+                .and_then(|lock| Some(lock.parent()?.to_path_buf()))
+        })
+}
+
+/// Arguments selecting a profile that isn't the default one, by name if it has one
+fn firefox_profile_args(dir: &Path, profiles: &FirefoxProfiles) -> Option<Vec<String>> {
+    if profiles.default.as_deref() == Some(dir) {
+        return None;
+    }
+    Some(match profiles.profiles.iter().find(|(_, path)| path == dir) {
+        Some((name, _)) => vec![String::from("-P"), name.clone()],
+        None => vec![String::from("--profile"), dir.to_string_lossy().into_owned()],
+    })
+}
+
+fn restore_firefox_profile(argv: &mut Vec<String>, pid: i32, profiles_dir: &str) -> bool {
+    if argv.iter().any(|arg| FIREFOX_PROFILE_ARGS.contains(&arg.as_str()) || arg.starts_with("--profile=")) {
+        return true;
+    }
+    let Some(dir) = firefox_profile_dir(pid) else {
+        return false;
+    };
+
+    let profiles_dir = home().join(profiles_dir);
+    let content = fs::read_to_string(profiles_dir.join("profiles.ini")).unwrap_or_default();
+    match firefox_profile_args(&dir, &parse_profiles_ini(&content, &profiles_dir)) {
+        Some(args) => {
+            argv.extend(args);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The user data directory in a window class such as `chromium (/home/user/.config/chromium-work)`
+fn chromium_data_dir_from_class(class: &str) -> Option<PathBuf> {
+    let dir = class.strip_suffix(')')?.rsplit_once(" (")?.1;
+    Path::new(dir).is_absolute().then(|| PathBuf::from(dir))
+}
+
+/// The user data directory and profile directories of files a Chromium process holds open.
+/// A user data directory is recognised by the `Local State` file at its root.
+fn chromium_open_profiles(files: &[PathBuf]) -> Option<(PathBuf, Vec<String>)> {
+    let mut data_dir: Option<PathBuf> = None;
+    let mut profiles: Vec<String> = vec![];
+    for file in files {
+        let Some(dir) = file.ancestors().skip(1).find(|dir| dir.join("Local State").is_file()) else {
+            continue;
+        };
+        if data_dir.as_deref().is_some_and(|data_dir| data_dir != dir) {
+            continue;
+        }
+        data_dir = Some(dir.to_path_buf());
+
+        let profile = file.strip_prefix(dir).ok().and_then(|rest| match rest.components().next() {
+            Some(Component::Normal(name)) if rest.components().count() > 1 => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        });
+        if let Some(profile) = profile.filter(|profile| dir.join(profile).join("Preferences").is_file()) {
+            if !profiles.contains(&profile) {
+                profiles.push(profile);
+            }
+        }
+    }
+    Some((data_dir?, profiles))
+}
+
+fn restore_chromium_profile(argv: &mut Vec<String>, client: &Client, default_data_dir: &str) -> bool {
+    let has_data_dir = argv.iter().any(|arg| arg.starts_with("--user-data-dir="));
+    let has_profile = argv.iter().any(|arg| arg.starts_with("--profile-directory="));
+    let (open_data_dir, profiles) = chromium_open_profiles(&process::open_files(client.pid)).unzip();
+
+    let default_data_dir = home().join(default_data_dir);
+    let data_dir = [&client.class, &client.initial_class]
+        .iter()
+        .find_map(|class| chromium_data_dir_from_class(class))
+        .or(open_data_dir)
+        .filter(|dir| *dir != default_data_dir);
+    if let Some(dir) = data_dir.as_ref().filter(|_| !has_data_dir) {
+        argv.push(format!("--user-data-dir={}", dir.display()));
+    }
+
+    // All profiles share one process so a profile is only known for certain when it is the only one open
+    let profile = match profiles.as_deref() {
+        Some([profile]) if profile != "Default" => Some(profile.clone()),
+        _ => None,
+    };
+    if let Some(profile) = profile.as_ref().filter(|_| !has_profile) {
+        argv.push(format!("--profile-directory={}", profile));
+    }
+
+    has_data_dir || has_profile || data_dir.is_some() || profile.is_some()
+}
+
+/// Make sure a browser is relaunched in the profile it was running with, opening a new window in it
+pub fn restore_profile(mut argv: Vec<String>, client: &Client) -> Vec<String> {
+    let Some(name) = argv.first().map(|program| executable_name(program).to_string()) else {
+        return argv;
+    };
+
+    let has_profile = if let Some((_, dir)) = FIREFOX_BROWSERS.iter().find(|(browser, _)| *browser == name) {
+        restore_firefox_profile(&mut argv, client.pid, dir)
+    } else if let Some((_, dir)) = CHROMIUM_BROWSERS.iter().find(|(browser, _)| *browser == name) {
+        restore_chromium_profile(&mut argv, client, dir)
+    } else {
+        false
+    };
+
+    // A browser already running another profile would otherwise only be raised
+    if has_profile && !argv.iter().any(|arg| arg == "--new-window") {
+        argv.push(String::from("--new-window"));
+    }
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles_ini() {
+        let content = "[Install4F96D1932A9F858E]\nDefault=abcd.default-release\nLocked=1\n\n\
+            [Profile1]\nName=work\nIsRelative=1\nPath=efgh.work\n\n\
+            [Profile0]\nName=default-release\nIsRelative=1\nPath=abcd.default-release\nDefault=1\n\n\
+            [Profile2]\nName=external\nIsRelative=0\nPath=/mnt/profiles/external\n\n\
+            [General]\nStartWithLastProfile=1\nVersion=2\n";
+        let dir = Path::new("/home/user/.mozilla/firefox");
+        let profiles = parse_profiles_ini(content, dir);
+
+        assert_eq!(profiles.default, Some(dir.join("abcd.default-release")));
+        assert_eq!(profiles.profiles.len(), 3);
+        assert_eq!(firefox_profile_args(&dir.join("abcd.default-release"), &profiles), None);
+        assert_eq!(firefox_profile_args(&dir.join("efgh.work"), &profiles).unwrap(), vec!["-P", "work"]);
+        assert_eq!(
+            firefox_profile_args(Path::new("/tmp/scratch"), &profiles).unwrap(),
+            vec!["--profile", "/tmp/scratch"]
+        );
+
+        let old = parse_profiles_ini("[Profile0]\nName=default\nIsRelative=1\nPath=x.default\nDefault=1\n", dir);
+        assert_eq!(old.default, Some(dir.join("x.default")));
+    }
+
+    #[test]
+    fn test_chromium_profiles() {
+        assert_eq!(
+            chromium_data_dir_from_class("chromium (/home/user/.config/chromium-work)"),
+            Some(PathBuf::from("/home/user/.config/chromium-work"))
+        );
+        assert!(chromium_data_dir_from_class("chromium").is_none());
+        assert!(chromium_data_dir_from_class("Some App (beta)").is_none());
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let dir = data_dir.path();
+        fs::write(dir.join("Local State"), "{}").unwrap();
+        for profile in ["Default", "Profile 2"] {
+            fs::create_dir(dir.join(profile)).unwrap();
+            fs::write(dir.join(profile).join("Preferences"), "{}").unwrap();
+        }
+
+        let files = vec![PathBuf::from("/usr/lib/libc.so.6"), dir.join("Profile 2/History"), dir.join("Local State")];
+        let (found, profiles) = chromium_open_profiles(&files).unwrap();
+        assert_eq!(found, dir);
+        assert_eq!(profiles, vec!["Profile 2"]);
+
+        let files = vec![dir.join("Default/Cookies"), dir.join("Profile 2/History")];
+        assert_eq!(chromium_open_profiles(&files).unwrap().1, vec!["Default", "Profile 2"]);
+        assert!(chromium_open_profiles(&[PathBuf::from("/usr/lib/libc.so.6")]).is_none());
+    }
+}
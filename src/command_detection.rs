@@ -1,108 +1,617 @@
 //use regex::Regex;
-use std::fs;
-use std::process::Command;
-use hyprland::data::{Client, Clients};
-use hyprland::shared::HyprData;
 use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use hyprland::data::Client;
+use serde::{Deserialize, Serialize};
+use crate::alias::{apply_alias, class_alias};
+use crate::arguments::filter_arguments;
+use crate::config::Config;
+use crate::container::handle_container;
+use crate::dbus::{handle_dbus, ServiceIndex};
+use crate::desktop_entry::{DesktopCache, DesktopIndex};
+use crate::documents::restore_documents;
+use crate::electron::handle_electron;
+use crate::{browser, process, shell};
+use crate::terminal::{new_window_command, restore_terminal};
+use crate::wine::{handle_steam, handle_wine};
+
+/// The command that restores a client
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedCommand {
+    pub argv: Vec<String>,
+    /// Directory the command must be started in, given by its desktop entry
+    pub cwd: Option<String>,
+    /// Whether the program was found in PATH, a desktop entry or the alias table rather than taken from the raw cmdline
+    pub resolved: bool,
+    /// Command opening the program's other windows, for terminals where it differs from the command of the first
+    pub window_argv: Option<Vec<String>>,
+}
+
+/// What one handler found for a client
+#[derive(Serialize, Default, Debug, Clone)]
+pub struct HandlerTrace {
+    pub handler: String,
+    pub argv: Option<Vec<String>>,
+    pub error: Option<String>,
+    /// Whether the program was found in PATH
+    pub in_path: bool,
+    /// Whether the command came from the alias table
+    pub aliased: bool,
+    /// Id of the desktop entry the program was found in when it isn't in PATH
+    pub desktop_entry: Option<String>,
+}
+
+/// How the command of a client was detected
+#[derive(Serialize, Debug, Clone)]
+pub struct DetectionReport {
+    pub address: String,
+    pub class: String,
+    pub initial_class: String,
+    pub initial_title: String,
+    pub pid: i32,
+    pub handlers: Vec<HandlerTrace>,
+    /// The command that would be saved
+    pub command: Option<Vec<String>>,
+    pub cwd: Option<String>,
+    pub error: Option<String>,
+}
 
-/// Check if a command exists in PATH using 'which'
-pub fn command_exists_in_path(command: &str) -> bool {
-    if command.is_empty() {
-        return false;
+impl DetectionReport {
+    pub fn print(&self) {
+        println!("{} {} (pid {}) \"{}\"", self.address, self.initial_class, self.pid, self.initial_title);
+        for trace in self.handlers.iter() {
+            let result = match (&trace.argv, &trace.error) {
+                (Some(argv), _) => {
+                    let found = match (&trace.desktop_entry, trace.in_path) {
+                        _ if trace.aliased => String::from("alias"),
+                        (_, true) => String::from("in PATH"),
+                        (Some(id), false) => format!("desktop entry {}", id),
+                        (None, false) => String::from("not found"),
+                    };
+                    format!("{} [{}]", shell::join(argv), found)
+                }
+                (None, Some(error)) => format!("error: {}", error),
+                (None, None) => String::new(),
+            };
+            println!("  {:<14} {}", trace.handler, result);
+        }
+        match (&self.command, &self.error) {
+            (Some(command), _) => println!("  decision: {}", shell::join(command)),
+            (None, Some(error)) => println!("  decision: none ({})", error),
+            (None, None) => println!("  decision: none"),
+        }
+        if let Some(cwd) = &self.cwd {
+            println!("  directory: {}", cwd);
+        }
     }
-    
-    Command::new("which")
-        .arg(command.split_whitespace().next().unwrap())
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
 }
 
-/// Extract the binary name from a command string
-fn extract_binary_name(command: &str) -> String {
-    let parts = command.split_whitespace();
-    let binary = parts
-        .clone()
-        .next()
-        .unwrap_or("")
-        .split('/')
-        .last()
-        .unwrap_or("");
+/// A way of detecting the command that started a client
+type Handler<'a> = &'a dyn Fn(&Client) -> Result<Vec<String>, std::io::Error>;
+
+/// Detected commands of running processes, kept between saves
+#[derive(Default)]
+pub struct DetectionCache {
+    /// Commands keyed by process id and the time the process started, as process ids are reused
+    commands: Mutex<HashMap<(i32, u64), DetectedCommand>>,
+    pub desktop: DesktopCache,
+}
 
-    format!("{} {}", binary, parts.skip(1).collect::<Vec<&str>>().join(" ")).trim().to_string()
+impl DetectionCache {
+    /// Forget processes other than the given ones
+    pub fn retain(&self, pids: &[i32]) {
+        self.commands.lock().unwrap().retain(|(pid, _), _| pids.contains(pid));
+    }
 }
 
-fn handle_proc_cmdline(client: &Client) -> Result<String, std::io::Error> {
-    let cmdline_path = format!("/proc/{}/cmdline", client.pid);
-    if let Ok(cmdline) = fs::read_to_string(&cmdline_path) {
-        let cleaned: String = cmdline
-            .replace('\0', " ")
-            .trim()
-            .to_string();
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
 
-        Ok(extract_binary_name(&cleaned))
-    } else {
-        Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Cmdline not found"))
+/// Find a program in a list of directories separated by colons, or check it directly if it is a path
+fn find_program(program: &str, search_path: &str) -> Option<PathBuf> {
+    if program.is_empty() {
+        return None;
+    }
+    if program.contains('/') {
+        return Some(PathBuf::from(program)).filter(|path| is_executable(path));
     }
+
+    search_path
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(program))
+        .find(|path| is_executable(path))
 }
 
-fn handle_proc_exe(client: &Client) -> Result<String, std::io::Error> {
+/// Check if a program exists in PATH, or is executable if it is given as a path
+pub fn command_exists_in_path(program: &str) -> bool {
+    find_program(program, &std::env::var("PATH").unwrap_or_default()).is_some()
+}
+
+/// Name of the executable a program path runs
+pub fn executable_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or("")
+}
+
+/// Replace the path of the program a command runs with its name
+fn extract_binary_name(mut args: Vec<String>) -> Vec<String> {
+    if let Some(program) = args.first_mut() {
+        *program = executable_name(program).to_string();
+    }
+    args
+}
+
+/// A command of a program followed by the given arguments
+fn with_args(program: &[&str], args: &[String]) -> Vec<String> {
+    program.iter().map(|part| part.to_string()).chain(args.iter().cloned()).collect()
+}
+
+fn handle_proc_cmdline(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    process::cmdline(client.pid)
+        .map(extract_binary_name)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Cmdline not found"))
+}
+
+fn handle_proc_exe(client: &Client) -> Result<Vec<String>, std::io::Error> {
     let exe_path = format!("/proc/{}/exe", client.pid);
     if let Ok(exe_target) = fs::read_link(&exe_path) {
         if let Some(exe_name) = exe_target.file_name() {
-            return Ok(exe_name.to_string_lossy().to_string());
+            return Ok(vec![exe_name.to_string_lossy().to_string()]);
         }
     }
     Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Exe not found"))
 }
 
-fn handle_initial_class(client: &Client) -> Result<String, std::io::Error> {
-    Ok(client.initial_class.to_lowercase())
+/// Read the application id from the `.flatpak-info` file at the root of a sandbox
+fn parse_flatpak_info(info: &str) -> Option<String> {
+    let mut in_application = false;
+    for line in info.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_application = line == "[Application]";
+        } else if let Some(name) = line.strip_prefix("name=").filter(|_| in_application) {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Read the application id from the scope flatpak runs an application in, e.g. `app-flatpak-org.gimp.GIMP-1234.scope`
+fn flatpak_app_id_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.rsplit('/').next())
+        .find_map(|unit| {
+            let (app_id, instance) = unit
+                .strip_prefix("app-flatpak-")?
+                .strip_suffix(".scope")?
+                .rsplit_once('-')?;
+            instance.chars().all(|c| c.is_ascii_digit()).then(|| app_id.to_string())
+        })
+}
+
+pub fn flatpak_command(app_id: &str, args: &[String]) -> Vec<String> {
+    with_args(&["flatpak", "run", app_id], args)
+}
+
+/// Id of the flatpak application a process runs in, if it runs in one
+pub fn flatpak_app_id(pid: i32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/root/.flatpak-info", pid))
+        .ok()
+        .and_then(|info| parse_flatpak_info(&info))
+        .or_else(|| {
+            fs::read_to_string(format!("/proc/{}/cgroup", pid))
+                .ok()
+                .and_then(|cgroup| flatpak_app_id_from_cgroup(&cgroup))
+        })
+}
+
+fn handle_flatpak(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    let app_id = flatpak_app_id(client.pid)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not a flatpak"))?;
+
+    // The binary path only exists inside the sandbox so only the arguments are kept
+    let args = process::cmdline(client.pid).unwrap_or_default();
+    Ok(flatpak_command(&app_id, args.get(1..).unwrap_or_default()))
+}
+
+/// Name of the snap a path belongs to, e.g. `firefox` for `/snap/firefox/current/usr/lib/firefox/firefox`
+fn snap_name_from_path(path: &str) -> Option<String> {
+    let name = path.strip_prefix("/snap/")?.split('/').next()?;
+    (!name.is_empty() && name != "bin").then(|| name.to_string())
+}
+
+fn snap_command(name: &str, args: &[String]) -> Vec<String> {
+    with_args(&["snap", "run", name], args)
+}
+
+/// Whether the process's executable lives inside the given directory.
+/// Used to ignore variables inherited from a snap or AppImage that launched an unrelated program.
+fn runs_from(pid: i32, dir: &str) -> bool {
+    process::exe(pid).is_some_and(|exe| exe.starts_with(dir))
+}
+
+fn handle_snap(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    let args = process::cmdline(client.pid).unwrap_or_default();
+    let environ = process::environ(client.pid);
+    let name = environ
+        .get("SNAP_INSTANCE_NAME")
+        .or_else(|| environ.get("SNAP_NAME"))
+        .filter(|_| environ.get("SNAP").is_some_and(|snap| runs_from(client.pid, snap)))
+        .cloned()
+        .or_else(|| snap_name_from_path(args.first()?))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not a snap"))?;
+
+    Ok(snap_command(&name, args.get(1..).unwrap_or_default()))
+}
+
+fn appimage_command(appimage: &str, args: &[String]) -> Vec<String> {
+    with_args(&[appimage], args)
+}
+
+/// AppImages run from a temporary mount so they are relaunched from the original file
+fn handle_appimage(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    let mut environ = process::environ(client.pid);
+    let appimage = environ
+        .remove("APPIMAGE")
+        .filter(|_| environ.get("APPDIR").is_none_or(|appdir| runs_from(client.pid, appdir)))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not an AppImage"))?;
+    let args = process::cmdline(client.pid).unwrap_or_default();
+
+    Ok(appimage_command(&appimage, args.get(1..).unwrap_or_default()))
 }
 
-fn handle_initial_title(client: &Client) -> Result<String, std::io::Error> {
-    Ok(client.initial_title.to_lowercase())
+/// Recover the program name from a Nix wrapper name in either the `.name-wrapped` or `hash-name-wrapped` form
+fn unwrap_nix_name(binary: &str) -> Option<String> {
+    let mut name = binary.strip_suffix("-wrapped")?;
+    while let Some(inner) = name.strip_suffix("-wrapped") {
+        name = inner;
+    }
+
+    let name = match name.strip_prefix('.') {
+        Some(dotted) => dotted.trim_start_matches('.'),
+        None => name.split_once('-')?.1,
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Replace a Nix store wrapper with the name of the program it wraps so that it's found in the profile's PATH
+/// rather than at a store path that garbage collection can remove
+fn handle_nix_wrapped(args: &[String]) -> Result<Vec<String>, std::io::Error> {
+    let name = args.first()
+        .and_then(|program| unwrap_nix_name(executable_name(program)))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not a Nix wrapper"))?;
+
+    Ok(with_args(&[&name], &args[1..]))
+}
+
+fn handle_nix(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    let args = process::cmdline(client.pid).unwrap_or_default();
+    handle_nix_wrapped(&args).or_else(|_| {
+        // Wrappers started with --argv0 only show the wrapped name in their executable's path
+        let exe = process::exe(client.pid)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Exe not found"))?;
+        let name = handle_nix_wrapped(&[exe.to_string_lossy().into_owned()])?;
+        Ok(with_args(&[&name[0]], args.get(1..).unwrap_or_default()))
+    })
+}
+
+/// How to find the command a launcher program runs in its arguments
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LauncherRule {
+    /// Subcommand that must come before the command, such as `app` for `uwsm app`
+    pub subcommand: Option<String>,
+    /// Options that take their value as a separate argument
+    pub value_options: Vec<String>,
+    /// Whether the command is a shell command line passed with `-c`
+    pub shell: bool,
+}
+
+/// Interpreters that run wrapper scripts, which are relaunched by the script's path
+const INTERPRETERS: &[&str] = &["sh", "bash", "dash", "zsh", "python", "python3", "perl"];
+
+fn rule(subcommand: Option<&str>, value_options: &[&str], shell: bool) -> LauncherRule {
+    LauncherRule {
+        subcommand: subcommand.map(String::from),
+        value_options: value_options.iter().map(|option| option.to_string()).collect(),
+        shell,
+    }
+}
+
+fn builtin_launcher(name: &str) -> Option<LauncherRule> {
+    match name {
+        "sh" | "bash" | "dash" | "zsh" => Some(rule(None, &[], true)),
+        "uwsm" => Some(rule(Some("app"), &["-s", "-a", "-u", "-d", "-t"], false)),
+        "app2unit" => Some(rule(None, &["-s", "-a", "-u", "-d", "-t", "-c"], false)),
+        "systemd-run" => Some(rule(None, &[
+            "-u", "--unit", "-p", "--property", "-E", "--setenv", "--slice", "--description", "-M", "--machine",
+            "--uid", "--gid", "--nice", "--working-directory",
+        ], false)),
+        "dbus-launch" => Some(rule(None, &["--config-file"], false)),
+        "dbus-run-session" => Some(rule(None, &["--config-file", "--dbus-daemon"], false)),
+        "env" => Some(rule(None, &["-u", "--unset", "-C", "--chdir", "-S"], false)),
+        "setsid" | "nohup" => Some(rule(None, &[], false)),
+        _ => None,
+    }
+}
+
+/// The rule of a launcher program, preferring ones given in the config
+fn launcher_rule(config: &Config, program: &str) -> Option<LauncherRule> {
+    let name = executable_name(program);
+    config.launchers.get(name).cloned().or_else(|| builtin_launcher(name))
+}
+
+/// Shell command lines that are a single command without expansions can be split into its arguments
+pub fn split_simple_command(line: &str) -> Option<Vec<String>> {
+    if line.contains(|c| ";&|<>$`(){}\n*?".contains(c)) {
+        return None;
+    }
+    shell::split(line).filter(|args| !args.is_empty())
+}
+
+/// The command a launcher was asked to run, found by skipping its own options and subcommand
+fn launched_command(args: &[String], rule: &LauncherRule) -> Option<Vec<String>> {
+    let mut rest = args.get(1..)?.iter();
+    if rule.shell {
+        rest.position(|arg| arg == "-c")?;
+        return rest.next().and_then(|line| split_simple_command(line));
+    }
+
+    let mut subcommand = rule.subcommand.as_deref();
+    let mut command = vec![];
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            command.extend(rest.by_ref());
+        } else if arg.starts_with('-') {
+            if rule.value_options.contains(arg) {
+                rest.next();
+            }
+        } else if subcommand.is_some() {
+            subcommand.filter(|name| name == arg)?;
+            subcommand = None;
+        } else if !arg.contains('=') {
+            // Everything from the first argument that isn't an option or a variable assignment belongs to the command
+            command.push(arg);
+            command.extend(rest.by_ref());
+        }
+    }
+
+    (subcommand.is_none() && !command.is_empty()).then(|| command.into_iter().cloned().collect())
+}
+
+/// The script an interpreter is running, so the script can be run again rather than the program it wraps
+fn wrapper_script(args: &[String]) -> Option<Vec<String>> {
+    if !INTERPRETERS.contains(&executable_name(&args[0])) {
+        return None;
+    }
+    let script = args.get(1).filter(|script| !script.starts_with('-'))?;
+    fs::metadata(script).ok().filter(|meta| meta.is_file())?;
+
+    let name = executable_name(script);
+    let program = if command_exists_in_path(name) { name } else { script.as_str() };
+    Some(with_args(&[program], &args[2..]))
+}
+
+/// The command a process in a launcher chain stands for, if it is a launcher or wrapper script
+fn chain_command(pid: i32, config: &Config) -> Option<Vec<String>> {
+    let args = process::cmdline(pid)?;
+    match launcher_rule(config, &args[0]) {
+        Some(rule) => launched_command(&args, &rule).or_else(|| wrapper_script(&args)),
+        None => wrapper_script(&args),
+    }
+}
+
+/// Processes between the launcher that started a window and the window's owner, outermost first.
+/// Owners that are launchers themselves are followed down to the program they started.
+fn launcher_chain(pid: i32, config: &Config) -> Vec<i32> {
+    let mut chain = vec![pid];
+    let mut current = pid;
+    while chain_command(current, config).is_some() {
+        match process::children(current).as_slice() {
+            [child] => {
+                current = child.pid;
+                chain.push(current);
+            }
+            _ => break,
+        }
+    }
+
+    let mut current = pid;
+    while let Some(parent) = process::stat(current).map(|stat| stat.ppid).filter(|ppid| *ppid > 1) {
+        if chain_command(parent, config).is_none() {
+            break;
+        }
+        chain.insert(0, parent);
+        current = parent;
+    }
+    chain
+}
+
+/// The outermost command in the chain of launchers and wrapper scripts that started a window
+fn handle_launcher(client: &Client, config: &Config) -> Result<Vec<String>, std::io::Error> {
+    launcher_chain(client.pid, config)
+        .into_iter()
+        .find_map(|pid| chain_command(pid, config))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not started by a launcher"))
+}
+
+fn handle_initial_class(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    Ok(vec![client.initial_class.to_lowercase()])
+}
+
+fn handle_initial_title(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    Ok(vec![client.initial_title.to_lowercase()])
 }
 
 /// Fetch command for a Hyprland client using multiple detection methods
-pub fn fetch_command(client: &Client, xdg_map: &HashMap<String, String>) -> Result<String, std::io::Error> {
-    let handlers = vec![
-        handle_proc_cmdline,
-        handle_proc_exe,
-        handle_initial_class,
-        handle_initial_title,
+pub fn fetch_command(client: &Client, desktop: &DesktopIndex, services: &ServiceIndex, cache: &DetectionCache, config: &Config)
+    -> Result<DetectedCommand, std::io::Error> {
+    // Terminals and documents change while a program runs so only the detected program is cached
+    let key = process::stat(client.pid).map(|stat| (client.pid, stat.start_time));
+    let cached = key.and_then(|key| cache.commands.lock().unwrap().get(&key).cloned());
+    let detected = match cached {
+        Some(detected) => detected,
+        None => {
+            let detected = detect_command(client, desktop, services, config)?;
+            if let Some(key) = key {
+                cache.commands.lock().unwrap().insert(key, detected.clone());
+            }
+            detected
+        }
+    };
+    Ok(complete_command(detected, client, config))
+}
+
+/// Add what a program is doing right now to its detected command and drop arguments that only applied to its current run
+fn complete_command(detected: DetectedCommand, client: &Client, config: &Config) -> DetectedCommand {
+    // Arguments are filtered before any are added so that those Hyprsession builds itself, such as a terminal's
+    // directory or a browser profile, are kept. Programs in terminals and open documents are filtered as they are added.
+    let argv = filter_arguments(detected.argv, config.compiled_filters());
+    let window_argv = new_window_command(&argv, config);
+    let argv = restore_terminal(argv, client.pid, config);
+    let argv = restore_documents(argv, client.pid, config);
+    let argv = browser::restore_profile(argv, client);
+    DetectedCommand { argv, window_argv, ..detected }
+}
+
+fn detect_command(client: &Client, desktop: &DesktopIndex, services: &ServiceIndex, config: &Config) -> Result<DetectedCommand, std::io::Error> {
+    trace_detection(client, desktop, services, config, false).1
+}
+
+/// Run the handlers in order until one finds a command that can be launched, or every handler if `all` is set.
+/// Returns what each handler found along with the decision.
+fn trace_detection(client: &Client, desktop: &DesktopIndex, services: &ServiceIndex, config: &Config, all: bool)
+    -> (Vec<HandlerTrace>, Result<DetectedCommand, std::io::Error>) {
+    let alias = |client: &Client| {
+        class_alias(config, &client.initial_class)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No alias for initial class"))
+    };
+    let launcher = |client: &Client| handle_launcher(client, config);
+    // Electron applications installed through Nix run from a wrapper too
+    let electron = |client: &Client| {
+        handle_electron(client, desktop, config).map(|command| handle_nix_wrapped(&command).unwrap_or(command))
+    };
+    let wine = |client: &Client| handle_wine(client, desktop, config);
+    let container = |client: &Client| handle_container(client, desktop, config);
+    let dbus = |client: &Client| handle_dbus(client, desktop, services, config);
+    let handlers: [(&str, Handler); 15] = [
+        ("alias", &alias),
+        ("steam", &handle_steam),
+        ("flatpak", &handle_flatpak),
+        ("snap", &handle_snap),
+        ("appimage", &handle_appimage),
+        ("container", &container),
+        ("dbus", &dbus),
+        ("launcher", &launcher),
+        ("electron", &electron),
+        ("nix", &handle_nix),
+        ("wine", &wine),
+        ("proc_cmdline", &handle_proc_cmdline),
+        ("proc_exe", &handle_proc_exe),
+        ("initial_class", &handle_initial_class),
+        ("initial_title", &handle_initial_title),
     ];
 
-    for handler in handlers {
-        if let Ok(command) = handler(client) {
-            if command_exists_in_path(&command) {
-                return Ok(command);
-            } else {
-                // Fallback: check in xdg_map
-                if let Some(xdg_command) = xdg_map.get(&command) {
-                    return Ok(xdg_command.clone());
+    let mut traces = vec![];
+    let mut decision = None;
+    for (name, handler) in handlers {
+        let mut trace = HandlerTrace { handler: name.to_string(), ..Default::default() };
+        match handler(client) {
+            Ok(found) => {
+                // Aliases of the detected program replace it wherever it was found
+                let argv = if name == "alias" { found.clone() } else { apply_alias(found.clone(), config) };
+                trace.aliased = name == "alias" || argv != found;
+                trace.in_path = argv.first().is_some_and(|program| command_exists_in_path(program));
+                // Fallback: check the desktop entries
+                let entry = argv.first().filter(|_| !trace.in_path && !trace.aliased).and_then(|program| desktop.find(program));
+                trace.desktop_entry = entry.map(|entry| entry.id.clone());
+                if decision.is_none() {
+                    decision = match entry {
+                        _ if trace.in_path || trace.aliased => Some(DetectedCommand { argv: argv.clone(), cwd: None, resolved: true, window_argv: None }),
+                        Some(entry) => Some(DetectedCommand { argv: entry.command(config), cwd: entry.path.clone(), resolved: true, window_argv: None }),
+                        None => None,
+                    };
                 }
+                trace.argv = Some(argv);
             }
+            Err(e) => trace.error = Some(e.to_string()),
+        }
+        traces.push(trace);
+        if decision.is_some() && !all {
+            break;
         }
     }
 
     // Fallback to cmdline even if not in PATH
-    handle_proc_cmdline(client)
+    let result = match decision {
+        Some(detected) => Ok(detected),
+        None => handle_proc_cmdline(client).map(|found| {
+            let argv = apply_alias(found.clone(), config);
+            DetectedCommand { resolved: argv != found, argv, cwd: None, window_argv: None }
+        }),
+    };
+    (traces, result)
+}
+
+/// Explain how the command of a client is detected, running every handler
+pub fn explain_detection(client: &Client, desktop: &DesktopIndex, services: &ServiceIndex, config: &Config) -> DetectionReport {
+    let (handlers, result) = trace_detection(client, desktop, services, config, true);
+    let (decision, error) = match result {
+        Ok(detected) => (Some(complete_command(detected, client, config)), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    DetectionReport {
+        address: client.address.to_string(),
+        class: client.class.clone(),
+        initial_class: client.initial_class.clone(),
+        initial_title: client.initial_title.clone(),
+        pid: client.pid,
+        handlers,
+        command: decision.as_ref().map(|detected| detected.argv.clone()),
+        cwd: decision.and_then(|detected| detected.cwd),
+        error,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn argv(line: &str) -> Vec<String> {
+        shell::split(line).unwrap()
+    }
+
+    fn extract_binary_name(command: &str) -> String {
+        shell::join(&super::extract_binary_name(argv(command)))
+    }
+
+    fn handle_nix_wrapped(command: &str) -> Result<String, std::io::Error> {
+        super::handle_nix_wrapped(&argv(command)).map(|args| shell::join(&args))
+    }
     
+    #[test]
+    fn test_executable_name() {
+        assert_eq!(executable_name("/usr/bin/firefox"), "firefox");
+        assert_eq!(executable_name("/opt/My App/app"), "app");
+        assert_eq!(executable_name("kitty"), "kitty");
+        assert_eq!(executable_name(""), "");
+    }
+
     #[test]
     fn test_extract_binary_name() {
         assert_eq!(extract_binary_name("/usr/bin/firefox"), "firefox");
         assert_eq!(extract_binary_name("code"), "code");
         assert_eq!(extract_binary_name("/usr/bin/firefox --new-window"), "firefox --new-window");
         assert_eq!(extract_binary_name("/nix/store/.firefox-wrapped"), ".firefox-wrapped");
+        assert_eq!(extract_binary_name("'/opt/My App/app' 'two words'"), "app 'two words'");
     }
     
-    /*
     #[test]
     fn test_handle_nix_wrapped() {
         // Test .name-wrapped format
@@ -124,33 +633,122 @@ mod tests {
         let cmd_simple = "/nix/store/randomhash/.chromium-wrapped";
         let result_simple = handle_nix_wrapped(cmd_simple).unwrap();
         assert_eq!(result_simple, "chromium");
+
+        // Programs wrapped more than once
+        let cmd_double = "/nix/store/hash-kitty-0.35/bin/..kitty-wrapped-wrapped";
+        assert_eq!(handle_nix_wrapped(cmd_double).unwrap(), "kitty");
+
+        assert!(handle_nix_wrapped("/usr/bin/firefox --new-window").is_err());
+        assert!(handle_nix_wrapped("/nix/store/hash-firefox/bin/firefox").is_err());
     }
-    
+
+    #[test]
+    fn test_launched_command() {
+        let config = Config::default();
+        let launched = |line: &str| {
+            let args = argv(line);
+            launched_command(&args, &launcher_rule(&config, &args[0]).unwrap()).map(|args| shell::join(&args))
+        };
+
+        assert_eq!(launched("uwsm app -s b -- firefox --new-window").unwrap(), "firefox --new-window");
+        assert_eq!(launched("uwsm app -a org.gnome.Nautilus nautilus").unwrap(), "nautilus");
+        assert_eq!(launched("systemd-run --user --scope -u app-foo foot -e htop").unwrap(), "foot -e htop");
+        assert_eq!(launched("env -u DISPLAY GDK_BACKEND=wayland gimp").unwrap(), "gimp");
+        assert_eq!(launched("dbus-launch --exit-with-session thunar").unwrap(), "thunar");
+        assert!(launched("uwsm start hyprland").is_none());
+        assert!(launched("setsid").is_none());
+
+        assert_eq!(launched("/bin/sh -c '  discord --start-minimized '").unwrap(), "discord --start-minimized");
+        assert_eq!(launched("sh -c 'mpv \"My Clip.mp4\"'").unwrap(), "mpv 'My Clip.mp4'");
+        assert!(launched("sh -c 'cd ~/src && code .'").is_none());
+        assert!(launched("sh -c 'mpv $HOME/clip.mp4'").is_none());
+
+        let config: Config = serde_json::from_str(r#"{ "launchers": { "runapp": { "value_options": ["-n"] } } }"#).unwrap();
+        let custom = launcher_rule(&config, "runapp").unwrap();
+        assert_eq!(launched_command(&argv("runapp -n 5 mpv"), &custom).unwrap(), vec!["mpv"]);
+    }
+
     #[test]
     fn test_handle_flatpak() {
-        let cmd = "flatpak run org.mozilla.firefox --new-window";
-        let result = handle_flatpak(cmd).unwrap();
-        assert_eq!(result, "flatpak run org.mozilla.firefox --new-window");
-        
-        let cmd_with_flags = "flatpak run --app-id=org.gimp.GIMP --file=test.jpg";
-        let result_with_flags = handle_flatpak(cmd_with_flags).unwrap();
-        assert_eq!(result_with_flags, "flatpak run --app-id=org.gimp.GIMP --file=test.jpg");
+        let info = "[Application]\nname=org.mozilla.firefox\nruntime=runtime/org.freedesktop.Platform/x86_64/23.08\n\n[Instance]\nname=other\n";
+        assert_eq!(parse_flatpak_info(info).unwrap(), "org.mozilla.firefox");
+        assert!(parse_flatpak_info("[Instance]\nname=other\n").is_none());
+
+        let cgroup = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.gimp.GIMP-2718.scope\n";
+        assert_eq!(flatpak_app_id_from_cgroup(cgroup).unwrap(), "org.gimp.GIMP");
+        assert!(flatpak_app_id_from_cgroup("0::/user.slice/app-firefox-12.scope").is_none());
+
+        let args = vec!["--new-window".to_string()];
+        assert_eq!(flatpak_command("org.mozilla.firefox", &args), argv("flatpak run org.mozilla.firefox --new-window"));
+        assert_eq!(flatpak_command("org.gimp.GIMP", &[]), argv("flatpak run org.gimp.GIMP"));
     }
-    
+
     #[test]
     fn test_handle_appimage() {
-        let cmd = "/home/user/Applications/Firefox.AppImage --profile test";
-        let result = handle_appimage(cmd).unwrap();
-        assert_eq!(result, "Firefox.AppImage");
+        let args = vec!["--profile".to_string(), "test".to_string()];
+        let result = appimage_command("/home/user/Applications/Firefox.AppImage", &args);
+        assert_eq!(result, argv("/home/user/Applications/Firefox.AppImage --profile test"));
     }
-    
+
     #[test]
     fn test_handle_snap() {
-        let cmd = "/snap/firefox/current/usr/lib/firefox/firefox --new-window";
-        let result = handle_snap(cmd).unwrap();
-        assert_eq!(result, "firefox");
+        let name = snap_name_from_path("/snap/firefox/current/usr/lib/firefox/firefox").unwrap();
+        assert_eq!(name, "firefox");
+        assert_eq!(snap_command(&name, &["--new-window".to_string()]), argv("snap run firefox --new-window"));
+        assert!(snap_name_from_path("/snap/bin/firefox").is_none());
+        assert!(snap_name_from_path("/usr/bin/firefox").is_none());
     }
-    */
+
+    #[test]
+    fn test_trace_detection() {
+        let mut client = crate::session::tests::test_client("0x1", "definitely-missing", "", 1);
+        client.pid = std::process::id() as i32;
+        let desktop = DesktopIndex::default();
+        let services = ServiceIndex::load_from(vec![]);
+        let config = Config::default();
+
+        let report = explain_detection(&client, &desktop, &services, &config);
+        let names: Vec<&str> = report.handlers.iter().map(|trace| trace.handler.as_str()).collect();
+        assert_eq!(names.len(), 15);
+        assert_eq!(names[0], "alias");
+        let cmdline = report.handlers.iter().find(|trace| trace.handler == "proc_cmdline").unwrap();
+        assert_eq!(cmdline.argv.as_ref().unwrap()[0], executable_name(&std::env::args().next().unwrap()));
+        assert!(report.handlers[0].error.is_some());
+        assert!(report.command.is_some());
+
+        // Detection for saving stops at the first handler that finds a launchable command
+        let (traces, result) = trace_detection(&client, &desktop, &services, &config, false);
+        assert!(traces.len() <= 15);
+        assert!(result.is_ok());
+
+        // Aliases for the initial class are used as they are, before any other handler
+        let mut config = Config::default();
+        config.aliases.insert(String::from("Definitely-Missing"), argv("definitely-missing-launcher --x"));
+        let (traces, result) = trace_detection(&client, &desktop, &services, &config, false);
+        assert_eq!(traces.len(), 1);
+        assert!(traces[0].aliased);
+        assert_eq!(result.unwrap().argv, argv("definitely-missing-launcher --x"));
+    }
+
+    #[test]
+    fn test_find_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("program");
+        let data = dir.path().join("data");
+        fs::write(&program, "#!/bin/sh\n").unwrap();
+        fs::write(&data, "").unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+
+        let search_path = format!("/definitely/missing::{}", dir.path().display());
+        assert_eq!(find_program("program", &search_path), Some(program.clone()));
+        assert_eq!(find_program(&program.to_string_lossy(), ""), Some(program));
+        assert!(find_program("data", &search_path).is_none());
+        assert!(find_program("subdir", &search_path).is_none());
+        assert!(find_program("program", "").is_none());
+        assert!(find_program("", &search_path).is_none());
+    }
+
     #[test]
     #[ignore] // Weird bug when running as a nix flake
     fn test_command_exists_in_path() {
@@ -1,192 +1,155 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
 use std::os::unix::fs::PermissionsExt;
-use std::collections::HashMap;
-
-pub fn fake_command(name: &str, command: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let home_dir = std::env::var("HOME")?;
-    let bin_path = PathBuf::from(home_dir).join(".local/bin");
-    
-    // Create directory if it doesn't exist
-    fs::create_dir_all(&bin_path)?;
-        
-    let file_path = bin_path.join(name);
-    if !command.is_empty() {
-        // Write the command to the file with shebang
-        let content = format!("#!/bin/sh\n{}", command);
-        fs::write(&file_path, content)?;
-
-        // Make the file executable
-        let mut perms = fs::metadata(&file_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&file_path, perms)?;
-    } else {
-        // Remove the file if command is empty
-        if file_path.exists() {
-            fs::remove_file(&file_path)?;
-        }
+use crate::alias::script_command;
+use crate::command_detection::{command_exists_in_path, executable_name};
+use crate::config::Config;
+use crate::shell;
+
+/// Line marking scripts written by Hyprsession so scripts of the same name written by anyone else are left alone
+const MARKER: &str = "# Bridge command generated by hyprsession";
+
+/// Directory bridge scripts are written to, `~/.local/bin` unless set in the config
+pub fn bin_dir(config: &Config) -> PathBuf {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    match config.bin_dir.as_deref() {
+        Some(dir) => match dir.strip_prefix("~/") {
+            Some(rest) => home.join(rest),
+            None => PathBuf::from(dir),
+        },
+        None => home.join(".local/bin"),
     }
-    Ok(())
 }
 
-/// Parse a .desktop file and extract the application name and command
-fn parse_desktop_file(path: &PathBuf) -> Option<(String, String)> {
-    let content = fs::read_to_string(path).ok()?;
-    let mut name: Option<String> = None;
-    let mut exec: Option<String> = None;
-    let mut in_desktop_entry = false;
-    
-    for line in content.lines() {
-        let line = line.trim();
-        
-        if line == "[Desktop Entry]" {
-            in_desktop_entry = true;
-            continue;
-        } else if line.starts_with('[') {
-            in_desktop_entry = false;
-            continue;
-        }
-        
-        if !in_desktop_entry {
-            continue;
-        }
-        
-        if let Some(value) = line.strip_prefix("Name=") {
-            name = Some(value.to_lowercase().to_string());
-        } else if let Some(value) = line.strip_prefix("Exec=") {
-            // Remove field codes like %f, %F, %u, %U, etc.
-            let cleaned = value
-                .split_whitespace()
-                .filter(|s| !s.starts_with('%'))
-                .collect::<Vec<_>>()
-                .join(" ");
-            exec = Some(cleaned);
-        }
-        
-        // Stop early if we have both
-        if name.is_some() && exec.is_some() {
-            break;
-        }
+/// Whether a script was written by Hyprsession
+pub fn is_generated(content: &str) -> bool {
+    content.lines().nth(1) == Some(MARKER)
+}
+
+/// Script running a command with the arguments the script was called with
+fn script(argv: &[String]) -> String {
+    format!("#!/bin/sh\n{}\nexec {} \"$@\"\n", MARKER, shell::join(argv))
+}
+
+fn generated_at(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| is_generated(&content))
+}
+
+/// Remove a file after checking it with `is_ours`
+fn remove_checked(bin_dir: &Path, name: &str, is_ours: impl Fn(&str) -> bool) -> Result<bool, Error> {
+    let file_path = bin_dir.join(name);
+    if !file_path.exists() {
+        return Ok(false);
     }
-    
-    match (name, exec) {
-        (Some(n), Some(e)) if !e.is_empty() => Some((n, e)),
-        _ => None,
+    if !fs::read_to_string(&file_path).is_ok_and(|content| is_ours(&content)) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} was not created by hyprsession", file_path.display()),
+        ));
     }
+    fs::remove_file(&file_path)?;
+    Ok(true)
 }
 
-/// Get XDG desktop file directories in priority order
-fn get_desktop_file_dirs() -> Vec<PathBuf> {
-    let mut dirs = Vec::new();
-    
-    // User-specific directory (highest priority)
-    if let Ok(data_home) = std::env::var("XDG_DATA_HOME") {
-        dirs.push(PathBuf::from(data_home).join("applications"));
-    } else if let Ok(home) = std::env::var("HOME") {
-        dirs.push(PathBuf::from(home).join(".local/share/applications"));
+/// Write a bridge script for a program. Programs of the same name in PATH or in the bin directory
+/// that weren't written by Hyprsession are only replaced if `overwrite` is set.
+pub fn fake_command(bin_dir: &Path, name: &str, argv: &[String], overwrite: bool) -> Result<PathBuf, Error> {
+    if name.is_empty() || name.contains('/') || argv.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "Invalid command name or command"));
+    }
+
+    let file_path = bin_dir.join(name);
+    // A script running a program of its own name would find itself in PATH and run itself forever
+    let program = &argv[0];
+    if (!program.starts_with('/') && executable_name(program) == name) || Path::new(program) == file_path {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("'{}' would run itself, give the program it should run as an absolute path", name),
+        ));
     }
-    
-    // System directories
-    if let Ok(data_dirs) = std::env::var("XDG_DATA_DIRS") {
-        for dir in data_dirs.split(':') {
-            if !dir.is_empty() {
-                dirs.push(PathBuf::from(dir).join("applications"));
-            }
-        }
-    } else {
-        // Default system directories
-        dirs.push(PathBuf::from("/usr/local/share/applications"));
-        dirs.push(PathBuf::from("/usr/share/applications"));
+    let is_ours = generated_at(&file_path);
+    if !overwrite && !is_ours && (file_path.exists() || command_exists_in_path(name)) {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("'{}' already exists in PATH, use --overwrite to replace it", name),
+        ));
     }
-    
-    dirs
+
+    fs::create_dir_all(bin_dir)?;
+    fs::write(&file_path, script(argv))?;
+    let mut perms = fs::metadata(&file_path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&file_path, perms)?;
+    Ok(file_path)
 }
 
-/// Create a map between application names and their commands from XDG .desktop files
-/// Returns a HashMap where keys are application names and values are the commands to run them
-pub fn build_xdg_command_map() -> HashMap<String, String> {
-    let mut map = HashMap::new();
-    
-    for dir in get_desktop_file_dirs() {
-        if !dir.exists() {
-            continue;
-        }
-        
-        if let Ok(entries) = fs::read_dir(&dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                
-                // Only process .desktop files
-                if path.extension().and_then(|s| s.to_str()) != Some("desktop") {
-                    continue;
-                }
-                
-                if let Some((name, command)) = parse_desktop_file(&path) {
-                    // Only add if not already present (respects priority order)
-                    map.entry(name).or_insert(command);
-                }
-            }
-        }
-    }
-    
-    map
+/// Remove a bridge script, refusing to remove files Hyprsession didn't write.
+/// Returns whether there was a script to remove.
+pub fn remove_command(bin_dir: &Path, name: &str) -> Result<bool, Error> {
+    remove_checked(bin_dir, name, is_generated)
+}
+
+/// Remove an unmarked bridge script of an earlier version once its command has been migrated
+pub fn remove_legacy_script(bin_dir: &Path, name: &str) -> Result<bool, Error> {
+    remove_checked(bin_dir, name, |content| script_command(content).is_some())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    #[ignore]
-    fn test_fake_command() {
-        let result = fake_command("firefox", "flatpak run org.mozilla.firefox");
-        assert!(result.is_ok());
-        assert!(std::path::PathBuf::from(std::env::var("HOME").unwrap())
-            .join(".local/bin/firefox")
-            .exists());
-        let cleanup = fake_command("firefox", "");
-        assert!(cleanup.is_ok());
-        assert!(!std::path::PathBuf::from(std::env::var("HOME").unwrap())
-            .join(".local/bin/firefox")
-            .exists());
+    fn argv(line: &str) -> Vec<String> {
+        shell::split(line).unwrap()
     }
 
     #[test]
-    fn test_build_xdg_command_map() {
-        let commands = build_xdg_command_map();
-        
-        // The map should not be empty on most systems
-        // We can't test specific applications since they vary by system
-        // But we can test the structure
-        for (name, command) in commands.iter().take(5) {
-            println!("Found: {} -> {}", name, command);
-            assert!(!name.is_empty());
-            assert!(!command.is_empty());
-        }
+    fn test_fake_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        let path = fake_command(&bin, "definitely-missing-app", &argv("echo 'two words'"), false).unwrap();
+        assert!(is_generated(&fs::read_to_string(&path).unwrap()));
+
+        // Arguments given to the script are passed on
+        let output = std::process::Command::new(&path).args(["a b", "c"]).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "two words a b c\n");
+
+        // Scripts written by Hyprsession are replaced, anything else needs overwrite
+        assert!(fake_command(&bin, "definitely-missing-app", &argv("true"), false).is_ok());
+        fs::write(bin.join("own-script"), "#!/bin/sh\necho mine\n").unwrap();
+        assert_eq!(fake_command(&bin, "own-script", &argv("true"), false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fake_command(&bin, "sh", &argv("true"), false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert!(fake_command(&bin, "own-script", &argv("true"), true).is_ok());
+
+        // Scripts can't run themselves, even with overwrite
+        assert_eq!(fake_command(&bin, "firefox", &argv("firefox -P work"), true).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(fake_command(&bin, "firefox", &argv("./firefox"), true).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let own_path = bin.join("firefox").to_string_lossy().into_owned();
+        assert_eq!(fake_command(&bin, "firefox", &[own_path], true).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(fake_command(&bin, "firefox", &argv("/usr/bin/firefox -P work"), true).is_ok());
+
+        fs::write(bin.join("user-script"), "#!/bin/sh\necho mine\n").unwrap();
+        assert!(remove_command(&bin, "user-script").is_err());
+        assert!(bin.join("user-script").exists());
+        assert!(remove_command(&bin, "definitely-missing-app").unwrap());
+        assert!(!remove_command(&bin, "definitely-missing-app").unwrap());
+
+        // Scripts from earlier versions are only removed when migrating
+        fs::write(bin.join("legacy"), "#!/bin/sh\nflatpak run org.app").unwrap();
+        assert!(remove_command(&bin, "legacy").is_err());
+        assert_eq!(fake_command(&bin, "legacy", &argv("true"), false).unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert!(remove_legacy_script(&bin, "user-script").is_err());
+        assert!(remove_legacy_script(&bin, "legacy").unwrap());
     }
 
     #[test]
-    fn test_parse_desktop_file() {
-        // Create a temporary .desktop file for testing
-        let temp_dir = std::env::temp_dir();
-        let test_file = temp_dir.join("test.desktop");
-        
-        let content = r#"[Desktop Entry]
-Name=Test Application
-Exec=test-app --flag %f
-Type=Application
-Icon=test-icon
-"#;
-        fs::write(&test_file, content).unwrap();
-        
-        let result = parse_desktop_file(&test_file);
-        assert!(result.is_some());
-        
-        let (name, command) = result.unwrap();
-        assert_eq!(name, "test application");
-        assert_eq!(command, "test-app --flag");
-        
-        // Cleanup
-        fs::remove_file(&test_file).unwrap();
+    fn test_bin_dir() {
+        let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+        let mut config = Config::default();
+        assert_eq!(bin_dir(&config), home.join(".local/bin"));
+        config.bin_dir = Some(String::from("~/bin"));
+        assert_eq!(bin_dir(&config), home.join("bin"));
+        config.bin_dir = Some(String::from("/opt/bridge"));
+        assert_eq!(bin_dir(&config), PathBuf::from("/opt/bridge"));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::arguments::{compile, ArgumentFilter, CompiledFilter};
+use crate::command_detection::{executable_name, LauncherRule};
+use crate::terminal::{ProgramFilter, TerminalProfile};
+
+/// How the windows of an application with more than one window are restored
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MultiWindowStrategy {
+    /// Launch the application once per window, adding the flag to every launch after the first
+    NewWindow(String),
+    /// Launch the application again for every window, as it opens a new window each time it is run
+    Relaunch,
+    /// Launch the application once and let it restore its own windows
+    SelfRestore,
+    /// Launch the application once and report its other windows as missing
+    Once,
+}
+
+/// Strategies for applications known to accept a new window flag and terminals, which open a window every time.
+/// Firefox and its forks restore their own windows, so launching more would open windows twice.
+fn builtin_multi_window(name: &str) -> Option<MultiWindowStrategy> {
+    match name {
+        "chromium" | "google-chrome-stable" | "google-chrome"
+            | "brave" | "vivaldi" | "microsoft-edge" | "code" | "codium" =>
+            Some(MultiWindowStrategy::NewWindow(String::from("--new-window"))),
+        "firefox" | "firefox-esr" | "librewolf" | "floorp" | "zen" | "zen-browser" | "waterfox" =>
+            Some(MultiWindowStrategy::SelfRestore),
+        "kitty" | "foot" | "footclient" | "alacritty" | "wezterm" | "wezterm-gui" | "ghostty" =>
+            Some(MultiWindowStrategy::Relaunch),
+        _ => None,
+    }
+}
+
+/// User configuration read from `$HYPRSESSION_CONFIG` or `~/.config/hyprsession/config.json`
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Multi-window strategies keyed by executable name, overriding the built-in ones
+    pub multi_window: HashMap<String, MultiWindowStrategy>,
+    /// Terminal emulators keyed by executable name, in addition to the built-in ones
+    pub terminals: HashMap<String, TerminalProfile>,
+    /// Programs that may be rerun inside terminals they were running in
+    pub terminal_programs: ProgramFilter,
+    /// Whether documents open in an application are reopened, keyed by executable name
+    pub documents: HashMap<String, bool>,
+    /// Environment variables recorded for every program and set again when it is launched
+    pub environment: Vec<String>,
+    /// Launcher programs keyed by executable name, in addition to the built-in ones
+    pub launchers: HashMap<String, LauncherRule>,
+    /// Filters applied to the arguments of every command before the built-in ones
+    pub argument_filters: Vec<ArgumentFilter>,
+    /// Commands used for windows, keyed by initial class or by the executable name of the detected command
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Directory bridge scripts are written to, `~/.local/bin` if missing
+    pub bin_dir: Option<String>,
+    /// The argument filters followed by the built-in ones, compiled when first needed
+    #[serde(skip)]
+    compiled_filters: OnceLock<Vec<CompiledFilter>>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        if let Ok(path) = std::env::var("HYPRSESSION_CONFIG") {
+            PathBuf::from(path)
+        } else if let Ok(config_home) = std::env::var("XDG_CONFIG_HOME") {
+            PathBuf::from(config_home).join("hyprsession/config.json")
+        } else {
+            PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".config/hyprsession/config.json")
+        }
+    }
+
+    /// Read the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Config {
+        let path = Self::path();
+        let config = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                eprintln!("Warning: Ignoring invalid config file {}: {}", path.display(), e);
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        };
+        // Invalid argument filters are reported once here rather than on every save
+        config.compiled_filters();
+        config
+    }
+
+    pub fn compiled_filters(&self) -> &[CompiledFilter] {
+        self.compiled_filters.get_or_init(|| compile(&self.argument_filters))
+    }
+
+    /// Write the alias table to the config file, keeping the rest of the file as it is
+    pub fn save_aliases(&self) -> Result<(), std::io::Error> {
+        self.write_aliases(&Self::path())
+    }
+
+    fn write_aliases(&self, path: &Path) -> Result<(), std::io::Error> {
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid config file {}: {}", path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::Value::Object(Default::default()),
+            Err(e) => return Err(e),
+        };
+        let Some(object) = config.as_object_mut() else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Config file {} is not an object", path.display())));
+        };
+        object.insert(String::from("aliases"), serde_json::to_value(&self.aliases)?);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&config)? + "\n")
+    }
+
+    /// How to restore the windows of a program, keyed on the name of its executable
+    pub fn multi_window_strategy(&self, program: &str) -> MultiWindowStrategy {
+        let name = executable_name(program);
+        self.multi_window.get(name).cloned()
+            .or_else(|| builtin_multi_window(name))
+            .unwrap_or(MultiWindowStrategy::Once)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_window_strategy() {
+        let config: Config = serde_json::from_str(r#"{
+            "multi_window": { "firefox": "self-restore", "foot": { "new-window": "--new" } }
+        }"#).unwrap();
+
+        assert_eq!(config.multi_window_strategy("firefox"), MultiWindowStrategy::SelfRestore);
+        assert_eq!(config.multi_window_strategy("/usr/bin/foot"), MultiWindowStrategy::NewWindow("--new".to_string()));
+        assert_eq!(config.multi_window_strategy("chromium"), MultiWindowStrategy::NewWindow("--new-window".to_string()));
+        assert_eq!(config.multi_window_strategy("gimp"), MultiWindowStrategy::Once);
+        assert_eq!(Config::default().multi_window_strategy("librewolf"), MultiWindowStrategy::SelfRestore);
+        assert_eq!(Config::default().multi_window_strategy("wezterm-gui"), MultiWindowStrategy::Relaunch);
+    }
+
+    #[test]
+    fn test_write_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hyprsession/config.json");
+        let mut config = Config::default();
+        config.aliases.insert(String::from("firefox"), vec![String::from("librewolf")]);
+        config.write_aliases(&path).unwrap();
+
+        std::fs::write(&path, r#"{ "documents": { "mpv": false }, "aliases": {} }"#).unwrap();
+        config.write_aliases(&path).unwrap();
+        let written: Config = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written.aliases, config.aliases);
+        assert_eq!(written.documents.get("mpv"), Some(&false));
+
+        std::fs::write(&path, "{ invalid").unwrap();
+        assert!(config.write_aliases(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ invalid");
+    }
+}
@@ -0,0 +1,175 @@
+use hyprland::data::Client;
+use std::fs;
+use crate::command_detection::executable_name;
+use crate::config::Config;
+use crate::desktop_entry::{DesktopEntry, DesktopIndex};
+use crate::process;
+
+/// Tools that run programs from a container on the host
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Distrobox,
+    Toolbox,
+}
+
+/// A container a process runs in
+#[derive(Debug, Clone, PartialEq)]
+struct Container {
+    tool: Tool,
+    name: String,
+}
+
+/// The container name in the `/run/.containerenv` file podman writes into every container
+fn parse_containerenv(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("name="))
+        .map(|name| name.trim_matches('"').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The container name conmon, the process podman runs each container under, was started with
+fn conmon_name(args: &[String]) -> Option<String> {
+    if args.first().map(|program| executable_name(program)) != Some("conmon") {
+        return None;
+    }
+    args.iter().enumerate().find_map(|(i, arg)| match arg.as_str() {
+        "-n" | "--name" => args.get(i + 1).cloned(),
+        _ => arg.strip_prefix("--name=").map(String::from),
+    })
+}
+
+fn conmon_ancestor(pid: i32) -> Option<String> {
+    let mut current = pid;
+    loop {
+        current = process::stat(current).map(|stat| stat.ppid).filter(|ppid| *ppid > 1)?;
+        if let Some(name) = process::cmdline(current).and_then(|args| conmon_name(&args)) {
+            return Some(name);
+        }
+    }
+}
+
+/// Whether a process has its own mount namespace, as containers do
+fn in_other_mount_namespace(pid: i32) -> bool {
+    let namespace = |pid: &str| fs::read_link(format!("/proc/{}/ns/mnt", pid)).ok();
+    namespace(&pid.to_string()).is_some_and(|ns| Some(ns) != namespace("self"))
+}
+
+/// The distrobox or toolbox container a process runs in
+fn container_of(pid: i32) -> Option<Container> {
+    if !in_other_mount_namespace(pid) {
+        return None;
+    }
+    let environ = process::environ(pid);
+    let root = format!("/proc/{}/root", pid);
+    let name = environ
+        .get("CONTAINER_ID")
+        .cloned()
+        .or_else(|| fs::read_to_string(format!("{}/run/.containerenv", root)).ok().and_then(|env| parse_containerenv(&env)))
+        .or_else(|| conmon_ancestor(pid))?;
+
+    let is_toolbox = environ.contains_key("TOOLBOX_PATH") || fs::metadata(format!("{}/run/.toolboxenv", root)).is_ok();
+    let tool = if is_toolbox { Tool::Toolbox } else { Tool::Distrobox };
+    Some(Container { tool, name })
+}
+
+/// The container a desktop entry exported by distrobox or toolbox runs its program in, and the program
+fn exported_from(entry: &DesktopEntry) -> Option<(&str, &str)> {
+    let args = &entry.exec;
+    let (container, program) = match executable_name(args.first()?) {
+        // distrobox-enter -n <name> -- <program> ...
+        "distrobox-enter" | "distrobox" => {
+            let name = args.iter().position(|arg| arg == "-n" || arg == "--name").and_then(|i| args.get(i + 1))?;
+            let program = args.iter().position(|arg| arg == "--").and_then(|i| args.get(i + 1))?;
+            (name, program)
+        }
+        // toolbox run -c <name> <program> ...
+        "toolbox" => {
+            let i = args.iter().position(|arg| arg == "-c" || arg == "--container")?;
+            (args.get(i + 1)?, args.get(i + 2)?)
+        }
+        _ => return None,
+    };
+    Some((container.as_str(), executable_name(program)))
+}
+
+/// The entry exported from a container for a window, by window class or by the program it runs
+fn exported_entry<'a>(desktop: &'a DesktopIndex, container: &Container, client: &Client, program: &str) -> Option<&'a DesktopEntry> {
+    let exported: Vec<&DesktopEntry> = desktop
+        .entries()
+        .iter()
+        .filter(|entry| exported_from(entry).is_some_and(|(name, _)| name == container.name))
+        .collect();
+    let by_class = exported.iter().find(|entry| {
+        entry.startup_wm_class.as_ref().is_some_and(|class| class.eq_ignore_ascii_case(&client.initial_class))
+    });
+    by_class
+        .or_else(|| exported.iter().find(|entry| exported_from(entry).is_some_and(|(_, exec)| exec == program)))
+        .copied()
+}
+
+/// Command entering a container to run a program
+fn container_command(container: &Container, args: &[String]) -> Vec<String> {
+    let enter: Vec<String> = match container.tool {
+        Tool::Distrobox => vec!["distrobox", "enter", &container.name, "--"],
+        Tool::Toolbox => vec!["toolbox", "run", "-c", &container.name],
+    }.into_iter().map(String::from).collect();
+    enter.into_iter().chain(args.iter().cloned()).collect()
+}
+
+/// Programs running in a distrobox or toolbox container are restored from the desktop file exported for them,
+/// or else run again in the container
+pub fn handle_container(client: &Client, desktop: &DesktopIndex, config: &Config) -> Result<Vec<String>, std::io::Error> {
+    let container = container_of(client.pid)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not in a container"))?;
+    let args = process::cmdline(client.pid)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Cmdline not found"))?;
+
+    if let Some(entry) = exported_entry(desktop, &container, client, executable_name(&args[0])) {
+        return Ok(entry.command(config));
+    }
+    Ok(container_command(&container, &args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_container_names() {
+        let containerenv = "engine=\"podman-4.9.3\"\nname=\"fedora-toolbox-40\"\nid=\"8d0b\"\nimage=\"registry.fedoraproject.org/fedora-toolbox:40\"\n";
+        assert_eq!(parse_containerenv(containerenv).unwrap(), "fedora-toolbox-40");
+        assert!(parse_containerenv("engine=\"podman\"\n").is_none());
+
+        let conmon = strings(&["/usr/bin/conmon", "--api-version", "1", "-c", "8d0b", "-n", "arch", "--exit-dir", "/run"]);
+        assert_eq!(conmon_name(&conmon).unwrap(), "arch");
+        assert!(conmon_name(&strings(&["podman", "-n", "arch"])).is_none());
+    }
+
+    #[test]
+    fn test_exported_entry() {
+        let parse = |id: &str, exec: &str| {
+            DesktopEntry::parse(id, &format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\n", id, exec)).unwrap()
+        };
+        let desktop = DesktopIndex::new(vec![
+            parse("arch-gimp", "/usr/bin/distrobox-enter -n arch -- gimp %U"),
+            parse("fedora-toolbox-40-inkscape", "toolbox run -c fedora-toolbox-40 inkscape %F"),
+            parse("gimp", "gimp %U"),
+        ]);
+        let distrobox = Container { tool: Tool::Distrobox, name: String::from("arch") };
+        let client = crate::session::tests::test_client("0x1", "gimp-2.10", "", 1);
+
+        assert_eq!(exported_entry(&desktop, &distrobox, &client, "gimp").unwrap().id, "arch-gimp");
+        assert!(exported_entry(&desktop, &distrobox, &client, "inkscape").is_none());
+
+        let toolbox = Container { tool: Tool::Toolbox, name: String::from("fedora-toolbox-40") };
+        assert_eq!(exported_entry(&desktop, &toolbox, &client, "inkscape").unwrap().id, "fedora-toolbox-40-inkscape");
+
+        assert_eq!(container_command(&distrobox, &strings(&["gimp", "a.png"])), strings(&["distrobox", "enter", "arch", "--", "gimp", "a.png"]));
+        assert_eq!(container_command(&toolbox, &strings(&["inkscape"])), strings(&["toolbox", "run", "-c", "fedora-toolbox-40", "inkscape"]));
+    }
+}
@@ -0,0 +1,181 @@
+use hyprland::data::Client;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crate::command_detection::{command_exists_in_path, executable_name};
+use crate::config::Config;
+use crate::desktop_entry::{DesktopEntry, DesktopIndex};
+use crate::{process, shell};
+
+/// Message buses that start services they are asked for
+const BUS_DAEMONS: &[&str] = &["dbus-daemon", "dbus-broker", "dbus-broker-launch"];
+
+/// Bus name of a service from the systemd unit the bus started it in, e.g. `dbus-:1.2-org.gnome.Nautilus@0.service`
+fn service_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup.lines().filter_map(|line| line.rsplit('/').next()).find_map(|unit| {
+        let unit = unit.replace("\\x2d", "-");
+        let unit = unit.strip_prefix("app-").unwrap_or(&unit);
+        let (_, rest) = unit.strip_prefix("dbus-:")?.split_once('-')?;
+        let name = rest.strip_suffix(".service")?.split('@').next()?;
+        (!name.is_empty()).then(|| name.to_string())
+    })
+}
+
+/// The bus name and command of a D-Bus service file
+fn parse_service_file(content: &str) -> Option<(String, Vec<String>)> {
+    let mut in_service = false;
+    let (mut name, mut exec) = (None, None);
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_service = line == "[D-BUS Service]";
+        } else if let Some((key, value)) = line.split_once('=').filter(|_| in_service) {
+            match key.trim() {
+                "Name" => name = Some(value.trim().to_string()),
+                "Exec" => exec = shell::split(value.trim()),
+                _ => {}
+            }
+        }
+    }
+    Some((name?, exec.filter(|exec| !exec.is_empty())?))
+}
+
+fn service_dirs() -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let data_home = std::env::var("XDG_DATA_HOME").map(PathBuf::from).unwrap_or_else(|_| home.join(".local/share"));
+    let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| String::from("/usr/local/share:/usr/share"));
+    std::iter::once(data_home)
+        .chain(data_dirs.split(':').filter(|dir| !dir.is_empty()).map(PathBuf::from))
+        .map(|dir| dir.join("dbus-1/services"))
+        .collect()
+}
+
+/// Bus names of the session services keyed by the program they run, read when first needed
+pub struct ServiceIndex {
+    dirs: Vec<PathBuf>,
+    by_program: OnceLock<HashMap<String, String>>,
+}
+
+impl ServiceIndex {
+    pub fn load() -> ServiceIndex {
+        Self::load_from(service_dirs())
+    }
+
+    /// Index the service files in directories given in priority order
+    pub fn load_from(dirs: Vec<PathBuf>) -> ServiceIndex {
+        ServiceIndex { dirs, by_program: OnceLock::new() }
+    }
+
+    /// Bus name of the session service that runs a program
+    fn service_for(&self, program: &str) -> Option<&str> {
+        let by_program = self.by_program.get_or_init(|| {
+            let mut by_program = HashMap::new();
+            let services = self.dirs
+                .iter()
+                .filter_map(|dir| fs::read_dir(dir).ok())
+                .flat_map(|entries| entries.flatten())
+                .filter_map(|entry| parse_service_file(&fs::read_to_string(entry.path()).ok()?));
+            for (name, exec) in services {
+                by_program.entry(executable_name(&exec[0]).to_string()).or_insert(name);
+            }
+            by_program
+        });
+        by_program.get(program).map(String::as_str)
+    }
+}
+
+/// Whether a process was started by the session bus rather than by the user
+fn is_activated(pid: i32, args: &[String], service: Option<&str>) -> bool {
+    let parent_is_bus = process::stat(pid)
+        .and_then(|stat| process::cmdline(stat.ppid))
+        .is_some_and(|parent| BUS_DAEMONS.contains(&executable_name(&parent[0])));
+    service.is_some() || parent_is_bus || args.iter().any(|arg| arg == "--gapplication-service")
+}
+
+/// A command that opens a window of an activatable application, through the bus if it is started there
+fn activation_command(entry: &DesktopEntry, config: &Config, gapplication: bool) -> Vec<String> {
+    match entry.dbus_activatable && gapplication {
+        true => entry.activation_command(),
+        false => entry.command(config),
+    }
+}
+
+/// Applications started by D-Bus activation exit when run with the arguments the bus gave them,
+/// so they are restored from their desktop entry, asking the bus to activate them where they support it
+pub fn handle_dbus(client: &Client, desktop: &DesktopIndex, services: &ServiceIndex, config: &Config) -> Result<Vec<String>, std::io::Error> {
+    let args = process::cmdline(client.pid).unwrap_or_default();
+    let cgroup_service = fs::read_to_string(format!("/proc/{}/cgroup", client.pid))
+        .ok()
+        .and_then(|cgroup| service_from_cgroup(&cgroup));
+    if args.is_empty() || !is_activated(client.pid, &args, cgroup_service.as_deref()) {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Not started by D-Bus"));
+    }
+
+    // Activatable applications have desktop file ids equal to their bus name
+    let program = executable_name(&args[0]);
+    let entry = cgroup_service
+        .or_else(|| services.service_for(program).map(String::from))
+        .and_then(|service| desktop.find(&service))
+        .or_else(|| desktop.find(&client.initial_class))
+        .or_else(|| desktop.find(program))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No desktop entry for D-Bus service"))?;
+    Ok(activation_command(entry, config, command_exists_in_path("gapplication")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_from_cgroup() {
+        assert_eq!(service_from_cgroup("0::/user.slice/user-1000.slice/user@1000.service/app.slice/dbus-:1.2-org.gnome.Nautilus@0.service\n").unwrap(), "org.gnome.Nautilus");
+        assert_eq!(service_from_cgroup("0::/user.slice/app-dbus\\x2d:1.2\\x2dorg.gnome.Calculator@1.service").unwrap(), "org.gnome.Calculator");
+        assert!(service_from_cgroup("0::/user.slice/user@1000.service/app.slice/app-hyprland-kitty-1234.scope").is_none());
+    }
+
+    #[test]
+    fn test_parse_service_file() {
+        let content = "[D-BUS Service]\nName=org.gnome.Nautilus\nExec=/usr/bin/nautilus --gapplication-service\n";
+        let (name, exec) = parse_service_file(content).unwrap();
+        assert_eq!(name, "org.gnome.Nautilus");
+        assert_eq!(exec, vec!["/usr/bin/nautilus", "--gapplication-service"]);
+        assert!(parse_service_file("[D-BUS Service]\nName=org.example.NoExec\n").is_none());
+    }
+
+    #[test]
+    fn test_service_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, system) = (dir.path().join("user"), dir.path().join("system"));
+        for path in [&user, &system] {
+            fs::create_dir_all(path).unwrap();
+        }
+        fs::write(user.join("org.example.Mine.service"), "[D-BUS Service]\nName=org.example.Mine\nExec=/opt/app\n").unwrap();
+        fs::write(system.join("org.example.App.service"), "[D-BUS Service]\nName=org.example.App\nExec=/usr/bin/app\n").unwrap();
+        fs::write(system.join("org.gnome.Nautilus.service"), "[D-BUS Service]\nName=org.gnome.Nautilus\nExec=/usr/bin/nautilus --gapplication-service\n").unwrap();
+
+        let services = ServiceIndex::load_from(vec![user.clone(), system]);
+        assert_eq!(services.service_for("nautilus").unwrap(), "org.gnome.Nautilus");
+        assert_eq!(services.service_for("app").unwrap(), "org.example.Mine");
+        assert!(services.service_for("firefox").is_none());
+
+        // The files are only read once
+        fs::write(user.join("org.mozilla.firefox.service"), "[D-BUS Service]\nName=org.mozilla.firefox\nExec=firefox\n").unwrap();
+        assert!(services.service_for("firefox").is_none());
+    }
+
+    #[test]
+    fn test_activation_command() {
+        let config = Config::default();
+        let activatable = DesktopEntry::parse("org.gnome.Nautilus", "[Desktop Entry]\nType=Application\nName=Files\n\
+            Exec=nautilus --new-window %U\nDBusActivatable=true\n").unwrap();
+        assert_eq!(activation_command(&activatable, &config, true), vec!["gapplication", "launch", "org.gnome.Nautilus"]);
+        assert_eq!(activation_command(&activatable, &config, false), vec!["nautilus", "--new-window"]);
+
+        let plain = DesktopEntry::parse("org.example.App", "[Desktop Entry]\nType=Application\nName=App\nExec=app\n").unwrap();
+        assert_eq!(activation_command(&plain, &config, true), vec!["app"]);
+
+        let no_exec = DesktopEntry::parse("org.example.Bus", "[Desktop Entry]\nType=Application\nName=Bus\nDBusActivatable=true\n").unwrap();
+        assert!(no_exec.is_launchable());
+        assert_eq!(no_exec.command(&config), vec!["gapplication", "launch", "org.example.Bus"]);
+    }
+}
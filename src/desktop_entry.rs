@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use crate::command_detection::{command_exists_in_path, executable_name};
+use crate::config::Config;
+use crate::terminal::in_terminal;
+
+/// Field codes replaced by the launcher, which have nothing to expand to when relaunching
+const FIELD_CODES: &[char] = &['f', 'F', 'u', 'U', 'i', 'c', 'k', 'd', 'D', 'n', 'N', 'v', 'm'];
+
+/// An application from a `.desktop` file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DesktopEntry {
+    /// Desktop file id, e.g. `org.gnome.Nautilus` for `org.gnome.Nautilus.desktop`
+    pub id: String,
+    pub name: String,
+    /// Name in the language of the current locale
+    pub localized_name: Option<String>,
+    /// Arguments of the Exec key with quoting and field codes removed
+    pub exec: Vec<String>,
+    pub try_exec: Option<String>,
+    /// Directory the program must be started in
+    pub path: Option<String>,
+    /// Whether the program runs in a terminal
+    pub terminal: bool,
+    pub hidden: bool,
+    pub no_display: bool,
+    pub startup_wm_class: Option<String>,
+    /// Whether the application is started over D-Bus under its desktop file id
+    pub dbus_activatable: bool,
+}
+
+/// Undo the escapes allowed in every string value
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            // Other escapes belong to the Exec key's own quoting
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Split an Exec value into arguments, removing quotes and field codes
+fn split_exec(exec: &str) -> Option<Vec<String>> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_with(String::new);
+            }
+            '\\' if quoted => match chars.next()? {
+                escaped @ ('"' | '`' | '$' | '\\') => arg.get_or_insert_with(String::new).push(escaped),
+                other => arg.get_or_insert_with(String::new).extend(['\\', other]),
+            },
+            ' ' | '\t' | '\n' if !quoted => args.extend(arg.take()),
+            _ => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    args.extend(arg);
+
+    Some(args.into_iter().filter_map(|arg| expand_field_codes(&arg)).collect())
+}
+
+/// Remove field codes from an argument, dropping arguments that are only a field code
+fn expand_field_codes(arg: &str) -> Option<String> {
+    let mut chars = arg.chars();
+    if let (Some('%'), Some(code), None) = (chars.next(), chars.next(), chars.next()) {
+        if FIELD_CODES.contains(&code) {
+            return None;
+        }
+    }
+
+    let mut result = String::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => result.push('%'),
+            Some(code) if FIELD_CODES.contains(&code) => {}
+            Some(other) => result.extend(['%', other]),
+            None => result.push('%'),
+        }
+    }
+    Some(result)
+}
+
+/// Locale names to look for localized keys under, most specific first
+fn locale_names() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default();
+    let locale = locale.split(['.', '@']).next().unwrap_or("");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return vec![];
+    }
+
+    let mut names = vec![locale.to_string()];
+    if let Some((lang, _)) = locale.split_once('_') {
+        names.push(lang.to_string());
+    }
+    names
+}
+
+impl DesktopEntry {
+    /// Parse the `[Desktop Entry]` group of a desktop file
+    pub fn parse(id: &str, content: &str) -> Option<DesktopEntry> {
+        let locales = locale_names();
+        let mut values: HashMap<&str, &str> = HashMap::new();
+        let mut in_desktop_entry = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_desktop_entry = line == "[Desktop Entry]";
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=').filter(|_| in_desktop_entry) {
+                // Only the first occurrence of a key counts
+                values.entry(key.trim()).or_insert(value.trim());
+            }
+        }
+
+        let string = |key: &str| values.get(key).map(|value| unescape(value));
+        let boolean = |key: &str| values.get(key) == Some(&"true");
+        if values.get("Type").is_some_and(|kind| *kind != "Application") {
+            return None;
+        }
+
+        Some(DesktopEntry {
+            id: id.to_string(),
+            name: string("Name")?,
+            localized_name: locales.iter().find_map(|locale| string(&format!("Name[{}]", locale))),
+            exec: string("Exec").and_then(|exec| split_exec(&exec)).unwrap_or_default(),
+            try_exec: string("TryExec"),
+            path: string("Path").filter(|path| !path.is_empty()),
+            terminal: boolean("Terminal"),
+            hidden: boolean("Hidden"),
+            no_display: boolean("NoDisplay"),
+            startup_wm_class: string("StartupWMClass"),
+            dbus_activatable: boolean("DBusActivatable"),
+        })
+    }
+
+    /// Whether the entry can be launched on this system
+    pub fn is_launchable(&self) -> bool {
+        !self.hidden && (!self.exec.is_empty() || self.dbus_activatable) && self.try_exec.as_deref().is_none_or(command_exists_in_path)
+    }
+
+    /// The command asking a D-Bus activatable application to open a window
+    pub fn activation_command(&self) -> Vec<String> {
+        vec![String::from("gapplication"), String::from("launch"), self.id.clone()]
+    }
+
+    /// The command running the entry's program, in a terminal if it needs one
+    pub fn command(&self, config: &Config) -> Vec<String> {
+        if self.exec.is_empty() {
+            self.activation_command()
+        } else if self.terminal {
+            in_terminal(config, &self.exec)
+        } else {
+            self.exec.clone()
+        }
+    }
+}
+
+/// Get XDG desktop file directories in priority order
+fn get_desktop_file_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    // User-specific directory (highest priority)
+    if let Ok(data_home) = std::env::var("XDG_DATA_HOME") {
+        dirs.push(PathBuf::from(data_home).join("applications"));
+    } else if let Ok(home) = std::env::var("HOME") {
+        dirs.push(PathBuf::from(home).join(".local/share/applications"));
+    }
+
+    // System directories
+    if let Ok(data_dirs) = std::env::var("XDG_DATA_DIRS") {
+        for dir in data_dirs.split(':') {
+            if !dir.is_empty() {
+                dirs.push(PathBuf::from(dir).join("applications"));
+            }
+        }
+    } else {
+        // Default system directories
+        dirs.push(PathBuf::from("/usr/local/share/applications"));
+        dirs.push(PathBuf::from("/usr/share/applications"));
+    }
+
+    dirs
+}
+
+/// Desktop files below a directory with their ids, where subdirectories become part of the id
+fn desktop_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            desktop_files(&path, &format!("{}{}-", prefix, file_name), files);
+        } else if let Some(id) = file_name.strip_suffix(".desktop") {
+            files.push((format!("{}{}", prefix, id), path));
+        }
+    }
+}
+
+/// Launchable desktop entries looked up by id, window class, name or executable name
+#[derive(Debug, Default)]
+pub struct DesktopIndex {
+    entries: Vec<DesktopEntry>,
+    by_id: HashMap<String, usize>,
+    by_class: HashMap<String, usize>,
+    by_name: HashMap<String, usize>,
+    by_exec: HashMap<String, usize>,
+}
+
+impl DesktopIndex {
+    /// Index the desktop files in the XDG data directories
+    pub fn load() -> DesktopIndex {
+        Self::load_from(&get_desktop_file_dirs())
+    }
+
+    /// Index the desktop files in directories given in priority order
+    pub fn load_from(dirs: &[PathBuf]) -> DesktopIndex {
+        let mut files = vec![];
+        for dir in dirs {
+            desktop_files(dir, "", &mut files);
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let entries = files
+            .into_iter()
+            // Entries in earlier directories hide those with the same id, even when they are hidden themselves
+            .filter(|(id, _)| seen.insert(id.clone()))
+            .filter_map(|(id, path)| DesktopEntry::parse(&id, &fs::read_to_string(path).ok()?))
+            .collect();
+        DesktopIndex::new(entries)
+    }
+
+    /// Index entries given in priority order
+    pub fn new(entries: Vec<DesktopEntry>) -> DesktopIndex {
+        let entries: Vec<DesktopEntry> = entries.into_iter().filter(|entry| entry.is_launchable()).collect();
+        let mut index = DesktopIndex::default();
+        for (i, entry) in entries.iter().enumerate() {
+            index.by_id.entry(entry.id.to_lowercase()).or_insert(i);
+            if let Some(class) = &entry.startup_wm_class {
+                index.by_class.entry(class.to_lowercase()).or_insert(i);
+            }
+            for name in std::iter::once(&entry.name).chain(entry.localized_name.as_ref()) {
+                index.by_name.entry(name.to_lowercase()).or_insert(i);
+            }
+            if let Some(program) = entry.exec.first() {
+                index.by_exec.entry(executable_name(program).to_lowercase()).or_insert(i);
+            }
+        }
+        index.entries = entries;
+        index
+    }
+
+    /// Find the entry for a window class, an application name or a command
+    pub fn find(&self, key: &str) -> Option<&DesktopEntry> {
+        let key = key.to_lowercase();
+        let exec = executable_name(&key).to_string();
+        self.by_id.get(&key)
+            .or_else(|| self.by_class.get(&key))
+            .or_else(|| self.by_name.get(&key))
+            .or_else(|| self.by_exec.get(&exec))
+            .map(|&i| &self.entries[i])
+    }
+
+    pub fn entries(&self) -> &[DesktopEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Modification times of directories holding desktop files and their subdirectories
+type Stamps = Vec<(PathBuf, Option<SystemTime>)>;
+
+fn directory_stamps(dir: &Path, stamps: &mut Stamps) {
+    stamps.push((dir.to_path_buf(), fs::metadata(dir).and_then(|meta| meta.modified()).ok()));
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            directory_stamps(&entry.path(), stamps);
+        }
+    }
+}
+
+/// The desktop index, rebuilt only when a directory holding desktop files changes
+#[derive(Default)]
+pub struct DesktopCache {
+    cached: Mutex<Option<(Stamps, Arc<DesktopIndex>)>>,
+}
+
+impl DesktopCache {
+    pub fn index(&self) -> Arc<DesktopIndex> {
+        self.index_of(&get_desktop_file_dirs())
+    }
+
+    fn index_of(&self, dirs: &[PathBuf]) -> Arc<DesktopIndex> {
+        let mut stamps = vec![];
+        for dir in dirs {
+            directory_stamps(dir, &mut stamps);
+        }
+
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((_, index)) = cached.as_ref().filter(|(cached_stamps, _)| *cached_stamps == stamps) {
+            return index.clone();
+        }
+        let index = Arc::new(DesktopIndex::load_from(dirs));
+        *cached = Some((stamps, index.clone()));
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let content = r#"[Desktop Entry]
+# A comment
+Name=Test Application
+Name[xx]=Localised
+Exec=test-app --flag %f
+StartupWMClass=TestApp
+Type=Application
+Icon=test-icon
+
+[Desktop Action new-window]
+Name=New Window
+Exec=test-app --new-window
+"#;
+        let entry = DesktopEntry::parse("org.example.Test", content).unwrap();
+        assert_eq!(entry.id, "org.example.Test");
+        assert_eq!(entry.name, "Test Application");
+        assert_eq!(entry.exec, vec!["test-app", "--flag"]);
+        assert_eq!(entry.startup_wm_class.as_deref(), Some("TestApp"));
+        assert!(!entry.terminal && !entry.hidden && !entry.no_display);
+
+        let link = DesktopEntry::parse("link", "[Desktop Entry]\nType=Link\nName=Link\nURL=https://example.com\n");
+        assert!(link.is_none());
+    }
+
+    #[test]
+    fn test_split_exec() {
+        assert_eq!(
+            split_exec(r#""/opt/My App/app" --name="a b" "quote \" and \\ slash" %U"#).unwrap(),
+            vec!["/opt/My App/app", "--name=a b", r#"quote " and \ slash"#]
+        );
+        assert_eq!(split_exec("app --progress=100%% --file=%f").unwrap(), vec!["app", "--progress=100%", "--file="]);
+        assert!(split_exec(r#"app "unterminated"#).is_none());
+
+        // Backslashes are escaped once for the string value and once for the quoting
+        let entry = DesktopEntry::parse("x", "[Desktop Entry]\nName=X\nExec=\"a\\\\\\\\b\" \\s\n").unwrap();
+        assert_eq!(entry.exec, vec!["a\\b"]);
+    }
+
+    #[test]
+    fn test_desktop_index() {
+        let entry = |id: &str, content: &str| DesktopEntry::parse(id, &format!("[Desktop Entry]\n{}", content)).unwrap();
+        let index = DesktopIndex::new(vec![
+            entry("org.gnome.Nautilus", "Name=Files\nExec=nautilus --new-window %U\nPath=/srv\n"),
+            entry("code", "Name=Visual Studio Code\nExec=/usr/share/code/code %F\nStartupWMClass=Code\n"),
+            entry("htop", "Name=Htop\nExec=htop\nTerminal=true\n"),
+            entry("removed", "Name=Removed\nExec=removed\nHidden=true\n"),
+            entry("missing", "Name=Missing\nExec=missing\nTryExec=definitely-missing-program\n"),
+        ]);
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.find("org.gnome.nautilus").unwrap().path.as_deref(), Some("/srv"));
+        assert_eq!(index.find("files").unwrap().id, "org.gnome.Nautilus");
+        assert_eq!(index.find("nautilus").unwrap().id, "org.gnome.Nautilus");
+        assert_eq!(index.find("Code").unwrap().command(&Config::default()), vec!["/usr/share/code/code"]);
+        assert!(index.find("htop").unwrap().terminal);
+        assert!(index.find("removed").is_none());
+        assert!(index.find("missing").is_none());
+    }
+
+    #[test]
+    fn test_desktop_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = [dir.path().to_path_buf()];
+        fs::write(dir.path().join("a.desktop"), "[Desktop Entry]\nName=A\nExec=a\n").unwrap();
+
+        let cache = DesktopCache::default();
+        let first = cache.index_of(&dirs);
+        assert_eq!(first.len(), 1);
+        assert!(Arc::ptr_eq(&first, &cache.index_of(&dirs)));
+
+        // Adding a subdirectory changes the directory's modification time
+        fs::create_dir(dir.path().join("vendor")).unwrap();
+        fs::write(dir.path().join("vendor/b.desktop"), "[Desktop Entry]\nName=B\nExec=b\n").unwrap();
+        let second = cache.index_of(&dirs);
+        assert_eq!(second.len(), 2);
+        assert_eq!(second.find("vendor-b").unwrap().name, "B");
+    }
+
+    #[test]
+    fn test_load_desktop_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, system) = (dir.path().join("user"), dir.path().join("system"));
+        fs::create_dir_all(system.join("kde")).unwrap();
+        fs::create_dir_all(&user).unwrap();
+        let write = |path: PathBuf, exec: &str| {
+            fs::write(path, format!("[Desktop Entry]\nType=Application\nName=App\nExec={}\n", exec)).unwrap()
+        };
+        write(system.join("org.example.App.desktop"), "system-app");
+        write(system.join("kde/okular.desktop"), "okular %U");
+        write(system.join("notes.txt"), "ignored");
+        write(user.join("org.example.App.desktop"), "user-app");
+        fs::write(user.join("hidden.desktop"), "[Desktop Entry]\nType=Application\nName=Hidden\nExec=hidden\nHidden=true\n").unwrap();
+        fs::write(system.join("hidden.desktop"), "[Desktop Entry]\nType=Application\nName=Hidden\nExec=hidden\n").unwrap();
+
+        let index = DesktopIndex::load_from(&[user, system]);
+        assert_eq!(index.entries().len(), 2);
+        // Entries in earlier directories take precedence, and subdirectories prefix the id
+        assert_eq!(index.find("org.example.App").unwrap().exec, vec!["user-app"]);
+        assert_eq!(index.find("kde-okular").unwrap().exec, vec!["okular"]);
+        assert!(index.find("hidden").is_none());
+        assert!(DesktopIndex::load_from(&[dir.path().join("missing")]).entries().is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::arguments::filter_arguments;
+use crate::command_detection::executable_name;
+use crate::config::Config;
+use crate::process;
+
+/// Applications whose open documents are reopened unless disabled in the config
+const DOCUMENT_APPS: &[&str] = &[
+    "zathura", "mpv", "imv", "evince", "okular", "eog", "loupe", "papers", "atril", "xreader",
+    "libreoffice", "soffice", "soffice.bin",
+];
+
+/// System locations that never hold user documents
+const EXCLUDED_ROOTS: &[&str] = &[
+    "/usr", "/lib", "/lib64", "/bin", "/sbin", "/etc", "/opt", "/var", "/run", "/tmp",
+    "/proc", "/sys", "/dev", "/nix", "/gnu", "/snap", "/app",
+];
+
+/// Directories within the home directory that hold application state rather than documents
+const EXCLUDED_HOME_DIRS: &[&str] = &[".cache", ".config", ".local", ".mozilla", ".var", ".nix-profile"];
+
+const EXCLUDED_EXTENSIONS: &[&str] = &["so", "lock", "ttf", "otf", "pcf", "db", "sqlite", "log"];
+
+fn is_enabled(config: &Config, name: &str) -> bool {
+    config.documents.get(name).copied().unwrap_or(DOCUMENT_APPS.contains(&name))
+}
+
+/// Whether a path is named like a document the user opened rather than a file used by the application
+fn is_document_path(path: &Path, home: &Path) -> bool {
+    let text = path.to_string_lossy();
+    if !path.is_absolute() || text.ends_with(" (deleted)") {
+        return false;
+    }
+    if EXCLUDED_ROOTS.iter().any(|root| path.starts_with(root)) {
+        return false;
+    }
+    if EXCLUDED_HOME_DIRS.iter().any(|dir| path.starts_with(home.join(dir))) {
+        return false;
+    }
+
+    // Shared libraries are often versioned, as in libfoo.so.1
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    if file_name.contains(".so.") || file_name.starts_with('.') {
+        return false;
+    }
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+    !EXCLUDED_EXTENSIONS.contains(&extension.as_str())
+}
+
+/// Whether a path is a document the user opened rather than a file used by the application
+fn is_document(path: &Path, home: &Path) -> bool {
+    is_document_path(path, home) && fs::metadata(path).is_ok_and(|meta| meta.is_file())
+}
+
+/// Make document arguments absolute and add documents the process opened itself, such as through a file dialog
+pub fn restore_documents(mut parts: Vec<String>, pid: i32, config: &Config) -> Vec<String> {
+    if !parts.first().is_some_and(|program| is_enabled(config, executable_name(program))) {
+        return parts;
+    }
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let cwd = process::cwd(pid);
+
+    for part in parts.iter_mut().skip(1).filter(|part| !part.starts_with('-')) {
+        if let Some(cwd) = cwd.as_ref().filter(|_| Path::new(part.as_str()).is_relative()) {
+            let path = cwd.join(part.as_str());
+            if is_document(&path, &home) {
+                *part = path.to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    let mut opened = vec![parts[0].clone()];
+    for path in process::open_files(pid).into_iter().filter(|path| is_document(path, &home)) {
+        let path = path.to_string_lossy().into_owned();
+        if !parts.contains(&path) && !opened.contains(&path) {
+            opened.push(path);
+        }
+    }
+    parts.extend(filter_arguments(opened, config.compiled_filters()).into_iter().skip(1));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_document() {
+        let home = Path::new("/home/user");
+        assert!(is_document_path(Path::new("/home/user/paper.pdf"), home));
+        assert!(is_document_path(Path::new("/mnt/data/Report.ODT"), home));
+        assert!(!is_document_path(Path::new("/home/user/.cache/thumb.png"), home));
+        assert!(!is_document_path(Path::new("/home/user/libfoo.so.1"), home));
+        assert!(!is_document_path(Path::new("/home/user/.hidden.pdf"), home));
+        assert!(!is_document_path(Path::new("/home/user/music.db"), home));
+        assert!(!is_document_path(Path::new("/home/user/paper.pdf (deleted)"), home));
+        assert!(!is_document_path(Path::new("/usr/share/fonts/a.ttf"), home));
+        assert!(!is_document_path(Path::new("/tmp/notes.txt"), home));
+        assert!(!is_document_path(Path::new("socket:[1234]"), home));
+
+        // Only existing files count
+        let readme = Path::new(env!("CARGO_MANIFEST_DIR")).join("README.md");
+        assert!(is_document(&readme, home));
+        assert!(!is_document(&readme.with_file_name("missing.pdf"), home));
+        assert!(!is_document(Path::new(env!("CARGO_MANIFEST_DIR")), home));
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        let config: Config = serde_json::from_str(r#"{ "documents": { "mpv": false, "gimp": true } }"#).unwrap();
+        assert!(is_enabled(&config, "zathura"));
+        assert!(is_enabled(&config, "gimp"));
+        assert!(!is_enabled(&config, "mpv"));
+        assert!(!is_enabled(&config, "firefox"));
+    }
+}
@@ -0,0 +1,155 @@
+use hyprland::data::Client;
+use crate::command_detection::{command_exists_in_path, executable_name};
+use crate::config::Config;
+use crate::desktop_entry::DesktopIndex;
+use crate::process;
+
+/// Prefixes of the arguments Chromium and Electron pass between their own processes
+const INTERNAL_FLAGS: &[&str] = &[
+    "--type=", "--field-trial-handle=", "--enable-crash-reporter", "--crashpad-handler-pid=",
+    "--mojo-platform-channel-handle=", "--shared-files", "--metrics-shmem-handle=", "--launch-time-ticks=",
+    "--lang=", "--num-raster-threads=", "--renderer-client-id=", "--time-ticks-at-unix-epoch=",
+    "--variations-seed-version", "--change-stack-guard-on-fork=", "--app-path=", "--user-agent-product=",
+    "--service-sandbox-type=", "--utility-sub-type=", "--gpu-preferences=", "--enable-main-frame-before-activation",
+    "--standard-schemes", "--secure-schemes", "--fetch-schemes", "--cors-schemes", "--bypasscsp-schemes",
+    "--streaming-schemes", "--code-cache-schemes", "--service-worker-schemes", "--first-renderer-process",
+    "--message-loop-type-ui", "--subproc-heap-profiling",
+];
+
+/// Browsers that name web app windows `<prefix>-<app>-<profile>`, with the commands they may be installed as
+const WEB_APP_BROWSERS: &[(&str, &[&str])] = &[
+    ("chrome", &["chromium", "google-chrome-stable", "google-chrome", "chromium-browser"]),
+    ("brave", &["brave", "brave-browser"]),
+    ("msedge", &["microsoft-edge", "microsoft-edge-stable"]),
+    ("vivaldi", &["vivaldi", "vivaldi-stable"]),
+];
+
+fn is_internal(arg: &str) -> bool {
+    INTERNAL_FLAGS.iter().any(|flag| arg.starts_with(flag))
+}
+
+/// Zygotes, renderers and other helpers are started with a `--type=` argument
+fn is_helper(args: &[String]) -> bool {
+    args.iter().any(|arg| arg.starts_with("--type="))
+}
+
+/// The main process of the Chromium based application owning a window, if it belongs to one
+fn main_process(pid: i32) -> Option<i32> {
+    let mut current = pid;
+    let mut args = process::cmdline(current)?;
+    let mut walked = false;
+    while is_helper(&args) {
+        current = process::stat(current)?.ppid;
+        args = process::cmdline(current)?;
+        walked = true;
+    }
+
+    let has_helpers = || {
+        process::children(current)
+            .iter()
+            .any(|child| process::cmdline(child.pid).is_some_and(|args| is_helper(&args)))
+    };
+    (walked || has_helpers()).then_some(current)
+}
+
+/// Split a web app window class such as `chrome-<id>-Default` into its browser prefix, app and profile
+fn parse_web_app_class(class: &str) -> Option<(&str, &str, &str)> {
+    let (prefix, rest) = class.split_once('-')?;
+    let (app, profile) = rest.rsplit_once('-')?;
+    let is_profile = profile == "Default" || profile.starts_with("Profile_");
+    let is_browser = WEB_APP_BROWSERS.iter().any(|(browser, _)| *browser == prefix);
+    (is_browser && is_profile && !app.is_empty()).then_some((prefix, app, profile))
+}
+
+/// Arguments opening a web app, by id for installed apps or by URL for windows opened with `--app=`
+fn web_app_args(app: &str, profile: &str) -> Vec<String> {
+    let mut args = vec![];
+    if profile != "Default" {
+        args.push(format!("--profile-directory={}", profile.replace('_', " ")));
+    }
+
+    // Installed apps have ids of 32 letters from a to p, otherwise the class is the URL's host and path
+    if app.len() == 32 && app.chars().all(|c| ('a'..='p').contains(&c)) {
+        args.push(format!("--app-id={}", app));
+    } else {
+        let (host, path) = app.split_once("__").unwrap_or((app, ""));
+        args.push(format!("--app=https://{}/{}", host, path.replace('_', "/")));
+    }
+    args
+}
+
+/// The program of the browser a web app belongs to, preferring the one that is running
+fn browser_program(prefix: &str, main: Option<i32>) -> String {
+    let candidates = WEB_APP_BROWSERS
+        .iter()
+        .find(|(browser, _)| *browser == prefix)
+        .map_or(&[][..], |(_, commands)| *commands);
+    let running = main
+        .and_then(process::cmdline)
+        .map(|args| executable_name(&args[0]).to_string());
+
+    running
+        .into_iter()
+        .chain(candidates.iter().map(|command| command.to_string()))
+        .find(|command| command_exists_in_path(command))
+        .unwrap_or_else(|| candidates.first().unwrap_or(&prefix).to_string())
+}
+
+/// The command of an Electron application without the flags it passes between its processes.
+/// Applications installed outside of PATH keep their full path.
+fn electron_command(args: &[String]) -> Vec<String> {
+    let name = executable_name(&args[0]);
+    let program = if command_exists_in_path(name) { name } else { args[0].as_str() };
+
+    std::iter::once(program.to_string())
+        .chain(args[1..].iter().filter(|arg| !is_internal(arg)).cloned())
+        .collect()
+}
+
+/// Detect the command of a Chromium web app or an Electron application from its main process
+pub fn handle_electron(client: &Client, desktop: &DesktopIndex, config: &Config) -> Result<Vec<String>, std::io::Error> {
+    let main = main_process(client.pid);
+    if let Some((prefix, app, profile)) = parse_web_app_class(&client.initial_class) {
+        let mut args = vec![browser_program(prefix, main)];
+        args.extend(web_app_args(app, profile));
+        return Ok(args);
+    }
+
+    let main = main.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not a Chromium based application"))?;
+    // Desktop files hold the wrapper scripts and flags that packagers launch Electron applications with
+    if let Some(entry) = desktop.find(&client.initial_class) {
+        return Ok(entry.command(config));
+    }
+    let args = process::cmdline(main)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Cmdline not found"))?;
+    Ok(electron_command(&args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_web_app_class() {
+        let class = "chrome-cinhimbnkkaeohfgghhklpknlkffjgod-Default";
+        let (prefix, app, profile) = parse_web_app_class(class).unwrap();
+        assert_eq!((prefix, app, profile), ("chrome", "cinhimbnkkaeohfgghhklpknlkffjgod", "Default"));
+        assert_eq!(web_app_args(app, profile), vec!["--app-id=cinhimbnkkaeohfgghhklpknlkffjgod"]);
+
+        let (_, app, profile) = parse_web_app_class("brave-discord.com__app-Profile_1").unwrap();
+        assert_eq!(web_app_args(app, profile), vec!["--profile-directory=Profile 1", "--app=https://discord.com/app"]);
+
+        assert!(parse_web_app_class("chromium").is_none());
+        assert!(parse_web_app_class("org.gnome-Nautilus").is_none());
+        assert!(parse_web_app_class("google-chrome").is_none());
+    }
+
+    #[test]
+    fn test_electron_command() {
+        let args: Vec<String> = [
+            "/opt/definitely-missing/Discord", "--type=renderer", "--enable-crash-reporter=1,discord",
+            "--start-minimized", "--field-trial-handle=3,i,123",
+        ].iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(electron_command(&args), vec!["/opt/definitely-missing/Discord", "--start-minimized"]);
+    }
+}
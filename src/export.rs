@@ -0,0 +1,92 @@
+use hyprland::data::Client;
+use std::collections::HashSet;
+use crate::session::client_rules;
+use crate::shell;
+
+/// Build the window matching part of a rule from the client's initial class and title
+fn window_matcher(client: &Client) -> String {
+    let mut matcher = format!("initialClass:^({})$", regex::escape(&client.initial_class));
+    if !client.initial_title.is_empty() {
+        matcher += &format!(", initialTitle:^({})$", regex::escape(&client.initial_title));
+    }
+    matcher
+}
+
+/// Convert saved clients into a sourceable Hyprland config of window rules.
+/// Only the first client with a given class and initial title gets rules as later ones
+/// would be indistinguishable to Hyprland.
+pub fn window_rules(clients: &[Client]) -> String {
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut config = String::from("# Generated by hyprsession\n");
+
+    for client in clients {
+        if client.initial_class.is_empty()
+            || !seen.insert((client.initial_class.clone(), client.initial_title.clone())) {
+            continue;
+        }
+
+        let matcher = window_matcher(client);
+        for rule in client_rules(client) {
+            config += &format!("windowrulev2 = {}, {}\n", rule, matcher);
+        }
+    }
+
+    config
+}
+
+/// Convert exec lines of a session into `exec-once` keywords for hyprland.conf
+pub fn exec_once(lines: &[String]) -> String {
+    let mut config = String::from("# Generated by hyprsession\n");
+    for line in lines {
+        // A single # starts a comment in Hyprland's config
+        config += &format!("exec-once = {}\n", line.replace('#', "##"));
+    }
+    config
+}
+
+/// Convert exec lines of a session into a standalone shell script that launches them via hyprctl
+pub fn shell_script(lines: &[String]) -> String {
+    let mut script = String::from("#!/bin/sh\n# Generated by hyprsession\n");
+    for line in lines {
+        script += &format!("hyprctl dispatch exec {}\n", shell::quote(line));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::test_client;
+
+    #[test]
+    fn test_window_rules() {
+        let clients = [
+            test_client("0x1", "Slack", "Slack", 3),
+            test_client("0x2", "Slack", "Slack", 4),
+            test_client("0x3", "org.gnome.Nautilus", "", 1),
+        ];
+        let config = window_rules(&clients);
+
+        assert!(config.contains("windowrulev2 = workspace 3 silent, initialClass:^(Slack)$, initialTitle:^(Slack)$\n"));
+        assert!(config.contains("windowrulev2 = float, initialClass:^(Slack)$, initialTitle:^(Slack)$\n"));
+        assert!(config.contains("windowrulev2 = move 10 20, initialClass:^(org\\.gnome\\.Nautilus)$\n"));
+        assert!(!config.contains("workspace 4"));
+    }
+
+    #[test]
+    fn test_exec_once() {
+        let lines = vec!["[workspace 1 silent] firefox".to_string()];
+        assert_eq!(exec_once(&lines), "# Generated by hyprsession\nexec-once = [workspace 1 silent] firefox\n");
+
+        let lines = vec!["[workspace 1 silent] mpv 'track #1.mp3'".to_string()];
+        assert!(exec_once(&lines).ends_with("exec-once = [workspace 1 silent] mpv 'track ##1.mp3'\n"));
+    }
+
+    #[test]
+    fn test_shell_script() {
+        let lines = vec!["[workspace 2 silent;float] sh -c 'echo hi'".to_string()];
+        let script = shell_script(&lines);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.ends_with("hyprctl dispatch exec '[workspace 2 silent;float] sh -c '\\''echo hi'\\'''\n"));
+    }
+}
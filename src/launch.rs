@@ -0,0 +1,221 @@
+use hyprland::data::{Client, Clients};
+use hyprland::prelude::*;
+use hyprland::shared::Address;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use crate::config::Config;
+use crate::session::{SessionEntry, WindowLaunch};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Outcome of restoring the programs of a session
+#[derive(Default, Debug)]
+pub struct LaunchReport {
+    /// Entries whose window appeared after the first launch
+    pub restored: Vec<String>,
+    /// Entries whose window only appeared after being relaunched
+    pub retried: Vec<String>,
+    /// Entries that never produced a window
+    pub missing: Vec<String>,
+    /// Windows the application was left to restore itself that didn't appear, which aren't failures of the load
+    pub not_self_restored: Vec<String>,
+    /// Entries whose window ended up on a different workspace to the saved one
+    pub misplaced: Vec<String>,
+    /// Windows found for each launch along with the workspace they were saved on
+    windows: Vec<(String, Address, i32)>,
+}
+
+impl LaunchReport {
+    pub fn print_summary(&self) {
+        println!("Restored: {}, retried: {}, missing: {}, misplaced: {}",
+            self.restored.len(), self.retried.len(), self.missing.len(), self.misplaced.len());
+        for command in self.retried.iter() {
+            println!(" - retried: {}", command);
+        }
+        for command in self.missing.iter() {
+            println!(" - missing: {}", command);
+        }
+        for command in self.not_self_restored.iter() {
+            println!(" - not restored by the application: {}", command);
+        }
+        for command in self.misplaced.iter() {
+            println!(" - misplaced: {}", command);
+        }
+    }
+
+    /// Record windows that are no longer on the workspace they were saved on
+    pub fn check_placement(&mut self) -> hyprland::Result<()> {
+        let clients = Clients::get()?;
+        for (command, address, workspace) in self.windows.iter() {
+            let placed = clients.iter()
+                .any(|client| client.address == *address && client.workspace.id == *workspace);
+            if !placed {
+                self.misplaced.push(command.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A window to be restored and the window that has been attributed to it
+struct Launch<'a> {
+    command: String,
+    launch: WindowLaunch<'a>,
+    attempts: u32,
+    window: Option<Address>,
+}
+
+/// Whether a newly opened window could be the expected one
+fn is_expected_window(expected: &Client, client: &Client) -> bool {
+    (!expected.initial_class.is_empty() && expected.initial_class.eq_ignore_ascii_case(&client.initial_class))
+        || (!expected.class.is_empty() && expected.class.eq_ignore_ascii_case(&client.class))
+}
+
+/// Attribute windows that have not been seen before to launches still waiting for one
+fn claim_windows(launches: &mut [Launch], clients: &[Client], seen: &mut HashSet<Address>) {
+    for client in clients {
+        if !seen.insert(client.address.clone()) {
+            continue;
+        }
+
+        if let Some(launch) = launches.iter_mut()
+            .find(|launch| launch.window.is_none() && is_expected_window(launch.launch.client, client)) {
+            launch.window = Some(client.address.clone());
+        }
+    }
+}
+
+fn dispatch_launch(launch: &Launch) -> hyprland::Result<()> {
+    if let Some(line) = &launch.launch.exec_line {
+        println!("Sending: dispatch exec {line}");
+        hyprland::dispatch!(Exec, line)?;
+    }
+    Ok(())
+}
+
+/// Launch every entry and wait for their windows, relaunching those that fail to appear.
+/// Each retry waits twice as long as the previous attempt before giving up.
+pub fn launch_entries(entries: &[SessionEntry], config: &Config, retries: u32, retry_delay: u64) -> hyprland::Result<LaunchReport> {
+    let mut seen: HashSet<Address> = Clients::get()?
+        .iter()
+        .map(|client| client.address.clone())
+        .collect();
+    let mut launches: Vec<Launch> = entries
+        .iter()
+        .flat_map(|entry| {
+            let strategy = config.multi_window_strategy(entry.program());
+            entry.window_launches(&strategy)
+                .into_iter()
+                .map(|launch| Launch { command: entry.command_line(), launch, attempts: 1, window: None })
+        })
+        .collect();
+
+    for launch in launches.iter() {
+        dispatch_launch(launch)?;
+    }
+
+    let mut wait = Duration::from_secs(retry_delay);
+    let mut deadline = Instant::now() + wait;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let clients: Vec<Client> = Clients::get()?.iter().cloned().collect();
+        claim_windows(&mut launches, &clients, &mut seen);
+
+        if launches.iter().all(|launch| launch.window.is_some()) {
+            break;
+        }
+
+        if Instant::now() >= deadline {
+            // Windows an application restores itself can't be relaunched individually
+            let pending: Vec<&mut Launch> = launches
+                .iter_mut()
+                .filter(|launch| launch.window.is_none() && launch.launch.exec_line.is_some() && launch.attempts <= retries)
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+
+            for launch in pending {
+                println!("No window from '{}' yet, relaunching (attempt {})", launch.command, launch.attempts + 1);
+                launch.attempts += 1;
+                dispatch_launch(launch)?;
+            }
+            wait *= 2;
+            deadline = Instant::now() + wait;
+        }
+    }
+
+    Ok(build_report(launches))
+}
+
+fn build_report(launches: Vec<Launch>) -> LaunchReport {
+    let mut report = LaunchReport::default();
+    for launch in launches {
+        let command = launch.command;
+        match (launch.window, launch.attempts) {
+            (None, _) if launch.launch.by_application => report.not_self_restored.push(command),
+            (None, _) => report.missing.push(command),
+            (Some(address), attempts) => {
+                report.windows.push((command.clone(), address, launch.launch.client.workspace.id));
+                if attempts == 1 {
+                    report.restored.push(command);
+                } else {
+                    report.retried.push(command);
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::test_client;
+
+    #[test]
+    fn test_claim_windows() {
+        let expected = [test_client("0x1", "firefox", "", 1), test_client("0x2", "kitty", "", 1)];
+        let mut launches: Vec<Launch> = expected
+            .iter()
+            .map(|client| Launch {
+                command: String::new(),
+                launch: WindowLaunch { client, exec_line: None, by_application: false },
+                attempts: 1,
+                window: None,
+            })
+            .collect();
+        let mut seen = HashSet::from([Address::new("0xa")]);
+
+        claim_windows(&mut launches, &[test_client("0xa", "kitty", "", 1), test_client("0xb", "Firefox", "", 1)], &mut seen);
+        assert_eq!(launches[0].window, Some(Address::new("0xb")));
+        assert_eq!(launches[1].window, None);
+
+        claim_windows(&mut launches, &[test_client("0xc", "kitty", "", 1)], &mut seen);
+        assert_eq!(launches[1].window, Some(Address::new("0xc")));
+    }
+
+    #[test]
+    fn test_build_report() {
+        let expected = [
+            test_client("0x1", "gimp", "", 1), test_client("0x2", "gimp", "", 2),
+            test_client("0x3", "foot", "", 1), test_client("0x4", "firefox", "", 1),
+        ];
+        let launch = |client, exec_line: Option<&str>, by_application: bool, window: Option<&str>| Launch {
+            command: String::from("cmd"),
+            launch: WindowLaunch { client, exec_line: exec_line.map(String::from), by_application },
+            attempts: 1,
+            window: window.map(Address::new),
+        };
+        let report = build_report(vec![
+            launch(&expected[0], Some("gimp"), false, Some("0xa")),
+            launch(&expected[1], None, false, None),
+            launch(&expected[2], Some("foot"), false, None),
+            launch(&expected[3], None, true, None),
+        ]);
+        assert_eq!(report.restored.len(), 1);
+        assert_eq!(report.not_self_restored.len(), 1);
+        // Windows of applications launched only once that don't come back are missing
+        assert_eq!(report.missing.len(), 2);
+    }
+}
@@ -60,6 +60,7 @@ pub fn main() -> hyprland::Result<()> {
         load_time: 30,
         simulate: args.simulate,
         save_duplicate_pids: args.save_duplicate_pids,
+        ..Default::default()
     };
 
     if args.save_interval == 0 {
@@ -67,10 +68,10 @@ pub fn main() -> hyprland::Result<()> {
         exit(1);
     }
 
-    create_dir_all(&session_path).expect(&format!("Failed to create session dir: {session_path}"));
+    create_dir_all(&session_path).unwrap_or_else(|_| panic!("Failed to create session dir: {session_path}"));
 
     match mode {
-        Mode::Default | Mode::LoadAndExit => sessions.load(""),
+        Mode::Default | Mode::LoadAndExit => sessions.load("").map(|_| ()),
         Mode::SaveAndExit | Mode::SaveOnly => sessions.save(""),
     }?;
 
@@ -1,4 +1,20 @@
 pub mod session;
+pub mod shell;
 pub mod command_detection;
 pub mod command_faker;
-pub mod legacy;
\ No newline at end of file
+pub mod legacy;
+pub mod process;
+pub mod config;
+pub mod desktop_entry;
+pub mod documents;
+pub mod electron;
+pub mod export;
+pub mod launch;
+pub mod terminal;
+pub mod arguments;
+pub mod browser;
+pub mod alias;
+pub mod suggest;
+pub mod wine;
+pub mod container;
+pub mod dbus;
@@ -4,14 +4,35 @@ use std::{env, thread, time};
 //use serde::Deserialize;
 use clap::{Parser, ValueEnum};
 
+pub mod alias;
+pub mod arguments;
+pub mod browser;
 pub mod command_detection;
 pub mod command_faker;
+pub mod config;
+pub mod container;
+pub mod dbus;
+pub mod desktop_entry;
+pub mod documents;
+pub mod electron;
+pub mod export;
+pub mod launch;
 pub mod legacy;
+pub mod process;
 pub mod session;
+pub mod shell;
+pub mod suggest;
+pub mod terminal;
+pub mod wine;
 
+use crate::config::Config;
 use crate::session::*;
-use crate::command_faker::fake_command;
-use crate::command_detection::command_exists_in_path;
+use crate::command_faker::{bin_dir, fake_command, remove_command, remove_legacy_script};
+use crate::command_detection::explain_detection;
+use crate::dbus::ServiceIndex;
+use crate::desktop_entry::DesktopIndex;
+use hyprland::data::Clients;
+use hyprland::shared::{Address, HyprData};
 
 #[derive(Copy, Clone, PartialEq, ValueEnum)]
 enum Mode {
@@ -33,8 +54,26 @@ enum Mode {
     /// Delete a session
     Delete,
 
-    /// Create a command to fake applications
+    /// Manage the commands used for applications (add, list, show, remove or migrate)
     Command,
+
+    /// Print a session as static Hyprland config
+    Export,
+
+    /// Show how the command of every open window is detected
+    Detect,
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum)]
+enum ExportFormat {
+    /// Window rules placing matching windows wherever they are opened from
+    WindowRules,
+
+    /// exec-once keywords that can be sourced from hyprland.conf
+    ExecOnce,
+
+    /// POSIX shell script launching the session with hyprctl
+    Script,
 }
 
 #[derive(Parser)]
@@ -67,18 +106,52 @@ struct Args {
     /// Only adjust exiting clients without launching new programs
     #[arg(long, default_value_t = false)]
     adjust_clients_only: bool,
+
+    /// Number of times a program that fails to open a window is relaunched (default: 2)
+    #[arg(long, default_value_t = 2)]
+    launch_retries: u32,
+
+    /// Seconds to wait for a window before relaunching, doubled after each retry (default: 10)
+    #[arg(long, default_value_t = 10)]
+    retry_delay: u64,
+
+    /// Format used when exporting a session
+    #[arg(short = 'f', long, value_enum, default_value_t = ExportFormat::WindowRules)]
+    format: ExportFormat,
+
+    /// Only detect windows of this class (for Detect mode)
+    #[arg(long)]
+    class: Option<String>,
+
+    /// Only detect the window with this address (for Detect mode)
+    #[arg(long)]
+    address: Option<String>,
+
+    /// Create a bridge script in the bin directory instead of a command in the config (for Command mode)
+    #[arg(long, default_value_t = false)]
+    script: bool,
+
+    /// Replace programs of the same name when creating a bridge script
+    #[arg(long, default_value_t = false)]
+    overwrite: bool,
+
+    /// Suggest commands for windows the named session couldn't resolve when it was saved (for Command mode)
+    #[arg(long, default_value_t = false)]
+    suggest: bool,
+
+    /// Print detection results as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
 }
 
 fn migration_check(session_path: &str) {
     let old_session = std::path::Path::new(session_path).join("exec.conf");
     let new_session = std::path::Path::new(session_path).join("default");
-    if old_session.exists() {
-        if std::path::Path::new(session_path).join("exec.conf").exists() && !new_session.join("exec.conf").exists() {
-            println!("Migrating session data from {} to {}", old_session.display(), new_session.display());
-            std::fs::create_dir_all(&new_session).unwrap();
-            std::fs::rename(old_session, new_session.join("exec.conf")).unwrap();
-            std::fs::write(new_session.join("clients.json"), "[]").unwrap();
-        }
+    if old_session.exists() && !new_session.join("exec.conf").exists() {
+        println!("Migrating session data from {} to {}", old_session.display(), new_session.display());
+        std::fs::create_dir_all(&new_session).unwrap();
+        std::fs::rename(old_session, new_session.join("exec.conf")).unwrap();
+        std::fs::write(new_session.join("clients.json"), "[]").unwrap();
     }
 }
 
@@ -96,8 +169,8 @@ fn main() -> hyprland::Result<()> {
         env::var("HOME").unwrap() + "/.local/share/hyprsession"
     };
 
-    println!("Using session path: {}", session_path);
-    create_dir_all(&session_path).expect(&format!("Failed to create session dir: {session_path}"));
+    eprintln!("Using session path: {}", session_path);
+    create_dir_all(&session_path).unwrap_or_else(|_| panic!("Failed to create session dir: {session_path}"));
     migration_check(&session_path);
 
     if args.save_interval == 0 {
@@ -111,6 +184,10 @@ fn main() -> hyprland::Result<()> {
         adjust_clients_only: args.adjust_clients_only,
         simulate: args.simulate,
         save_duplicate_pids: false,
+        launch_retries: args.launch_retries,
+        retry_delay: args.retry_delay,
+        config: Config::load(),
+        ..Default::default()
     };
 
     match args.mode {
@@ -118,7 +195,10 @@ fn main() -> hyprland::Result<()> {
             session.clear()?;
         }
         Mode::Default | Mode::Load => {
-            session.load(&args.name)?;
+            let report = session.load(&args.name)?;
+            if args.mode == Mode::Load && !report.missing.is_empty() {
+                exit(1);
+            }
         }
         Mode::List => {
             println!("Available sessions:");
@@ -138,25 +218,163 @@ fn main() -> hyprland::Result<()> {
             session.save(&args.name)?;
         }
         Mode::Command => {
-            let command_name = args.name.clone();
-            if command_name.is_empty() {
-                eprintln!("Error: Command name cannot be empty");
-                exit(1);
+            if args.suggest {
+                let unresolved = session.unresolved_clients(&args.name);
+                if unresolved.is_empty() {
+                    println!("No unresolved windows in session '{}'", args.name);
+                    exit(0);
+                }
+                for (client, suggestion) in unresolved.iter().zip(suggest::suggest_commands(&unresolved)) {
+                    println!("{} (saved as: {})", client.initial_class, shell::join(&client.command));
+                    match suggestion {
+                        Some(suggestion) => {
+                            let command = shell::join(&suggestion.command);
+                            println!("  found {} in {} desktop entries", suggestion.desktop_entry, suggestion.package.name());
+                            println!("  hyprsession command {} {}", shell::quote(&client.initial_class), shell::quote(&command));
+                        }
+                        None => println!("  no suggestion found"),
+                    }
+                }
+                exit(0);
             }
 
-            if command_exists_in_path(&command_name) {
-                eprintln!("Command '{}' already exists in PATH.", command_name);
-                exit(1);
+            let bin_path = bin_dir(&session.config);
+            if args.script {
+                let (name, argv) = match args.name.as_str() {
+                    "remove" => (&args.command, Some(vec![])),
+                    _ => (&args.name, shell::split(&args.command)),
+                };
+                let result = match argv {
+                    Some(argv) if argv.is_empty() => remove_command(&bin_path, name).map(|removed| match removed {
+                        true => println!("Bridge script '{}' removed.", name),
+                        false => println!("No bridge script named '{}'", name),
+                    }),
+                    Some(argv) => fake_command(&bin_path, name, &argv, args.overwrite)
+                        .map(|path| println!("Bridge script created at {}", path.display())),
+                    None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid command '{}'", args.command))),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to update bridge script '{}': {}", name, e);
+                    exit(1);
+                }
+                exit(0);
             }
 
-            match fake_command(&command_name, &args.command.clone()) {
-                Ok(_) => {
-                    println!("Fake command '{}' created successfully.", command_name);
+            let mut config = session.config.clone();
+            let mut migrated = vec![];
+            match args.name.as_str() {
+                "list" => {
+                    for (name, argv) in config.aliases.iter() {
+                        println!("{}: {}", name, shell::join(argv));
+                    }
+                    if config.aliases.is_empty() {
+                        println!("(No commands found)");
+                    }
+                    exit(0);
+                }
+                "show" => match config.aliases.get(&args.command) {
+                    Some(argv) => {
+                        println!("{}", shell::join(argv));
+                        exit(0);
+                    }
+                    None => {
+                        eprintln!("No command named '{}'", args.command);
+                        exit(1);
+                    }
+                },
+                "remove" => {
+                    if config.aliases.remove(&args.command).is_none() {
+                        eprintln!("No command named '{}'", args.command);
+                        exit(1);
+                    }
+                    println!("Command '{}' removed.", args.command);
                 }
-                Err(e) => {
-                    eprintln!("Failed to create command '{}': {}", command_name, e);
+                "migrate" => {
+                    for (name, argv) in alias::bridge_scripts(&bin_path) {
+                        if config.aliases.contains_key(&name) {
+                            eprintln!("Skipping '{}': a command with that name already exists", name);
+                            continue;
+                        }
+                        if alias::RESERVED_NAMES.contains(&name.as_str()) {
+                            eprintln!("Skipping '{}': the name is reserved for a subcommand", name);
+                            continue;
+                        }
+                        println!("Migrating '{}': {}", name, shell::join(&argv));
+                        config.aliases.insert(name.clone(), argv);
+                        migrated.push(name);
+                    }
+                }
+                "" => {
+                    eprintln!("Error: Command name cannot be empty");
+                    exit(1);
+                }
+                name if args.command.is_empty() => {
+                    eprintln!("Error: No command given for '{}', use 'hyprsession command remove {}' to remove it", name, name);
                     exit(1);
                 }
+                name => match shell::split(&args.command).filter(|argv| !argv.is_empty()) {
+                    Some(argv) => {
+                        config.aliases.insert(name.to_string(), argv);
+                        println!("Command '{}' set to: {}", name, args.command);
+                    }
+                    None => {
+                        eprintln!("Error: Invalid command '{}'", args.command);
+                        exit(1);
+                    }
+                },
+            }
+
+            if let Err(e) = config.save_aliases() {
+                eprintln!("Failed to save commands to {}: {}", Config::path().display(), e);
+                exit(1);
+            }
+            // Scripts are only deleted once their commands are safely in the config
+            for name in migrated {
+                if let Err(e) = remove_legacy_script(&bin_path, &name) {
+                    eprintln!("Failed to remove script '{}': {}", name, e);
+                }
+            }
+        }
+        Mode::Export => {
+            let clients = session.saved_clients(&args.name);
+            let lines = session.exec_lines(&args.name);
+            let exported = match args.format {
+                ExportFormat::WindowRules => (!clients.is_empty()).then(|| export::window_rules(&clients)),
+                ExportFormat::ExecOnce => (!lines.is_empty()).then(|| export::exec_once(&lines)),
+                ExportFormat::Script => (!lines.is_empty()).then(|| export::shell_script(&lines)),
+            };
+
+            match exported {
+                Some(config) => print!("{}", config),
+                None => {
+                    eprintln!("No saved programs found for session '{}'", args.name);
+                    exit(1);
+                }
+            }
+        }
+        Mode::Detect => {
+            let address = args.address.as_ref().map(Address::new);
+            let desktop = DesktopIndex::load();
+            let services = ServiceIndex::load();
+            let reports: Vec<_> = Clients::get()?
+                .iter()
+                .filter(|client| args.class.as_ref().is_none_or(|class| {
+                    client.class.eq_ignore_ascii_case(class) || client.initial_class.eq_ignore_ascii_case(class)
+                }))
+                .filter(|client| address.as_ref().is_none_or(|address| client.address == *address))
+                .map(|client| explain_detection(client, &desktop, &services, &session.config))
+                .collect();
+
+            if reports.is_empty() {
+                eprintln!("No matching windows found");
+                exit(1);
+            }
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&reports).expect("Failed to serialise detection results"));
+            } else {
+                for report in reports.iter() {
+                    report.print();
+                }
             }
         }
     }
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Fields of `/proc/<pid>/stat` used to navigate the process tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcStat {
+    pub pid: i32,
+    pub comm: String,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub tty_nr: i32,
+    /// Process group in the foreground of the process's controlling terminal
+    pub tpgid: i32,
+    /// Time the process started after boot in clock ticks
+    pub start_time: u64,
+}
+
+/// Parse the contents of `/proc/<pid>/stat`
+pub fn parse_stat(data: &str) -> Option<ProcStat> {
+    // The command name is in brackets and may itself contain spaces or brackets
+    let open = data.find('(')?;
+    let close = data.rfind(')')?;
+    let pid = data[..open].trim().parse().ok()?;
+    let comm = data[open + 1..close].to_string();
+    let fields: Vec<&str> = data[close + 1..].split_whitespace().collect();
+
+    Some(ProcStat {
+        pid,
+        comm,
+        ppid: fields.get(1)?.parse().ok()?,
+        pgrp: fields.get(2)?.parse().ok()?,
+        tty_nr: fields.get(4)?.parse().ok()?,
+        tpgid: fields.get(5)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+pub fn stat(pid: i32) -> Option<ProcStat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// Every process currently running
+pub fn all_processes() -> Vec<ProcStat> {
+    fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .filter_map(stat)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Direct children of a process
+pub fn children(pid: i32) -> Vec<ProcStat> {
+    all_processes()
+        .into_iter()
+        .filter(|process| process.ppid == pid)
+        .collect()
+}
+
+/// Current working directory of a process
+pub fn cwd(pid: i32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// Path of the executable a process is running
+pub fn exe(pid: i32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/exe", pid)).ok()
+}
+
+/// Arguments of a process as they were passed to it
+pub fn cmdline(pid: i32) -> Option<Vec<String>> {
+    let data = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    // Every argument is terminated by a NUL, so arguments that are empty strings are kept
+    let data = data.strip_suffix(&[0]).unwrap_or(&data);
+    if data.is_empty() {
+        return None;
+    }
+
+    Some(data
+        .split(|&b| b == 0)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect())
+}
+
+/// Files held open by a process, in the order of their descriptors
+pub fn open_files(pid: i32) -> Vec<PathBuf> {
+    let mut fds: Vec<(u32, PathBuf)> = fs::read_dir(format!("/proc/{}/fd", pid))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let fd = entry.file_name().to_str()?.parse().ok()?;
+                    Some((fd, fs::read_link(entry.path()).ok()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    fds.sort();
+    fds.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Environment a process was started with
+pub fn environ(pid: i32) -> HashMap<String, String> {
+    fs::read(format!("/proc/{}/environ", pid))
+        .map(|data| {
+            data.split(|&b| b == 0)
+                .filter_map(|var| {
+                    let var = String::from_utf8_lossy(var);
+                    let (name, value) = var.split_once('=')?;
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let data = "4242 (tmux: server (1)) S 4200 4242 4200 34817 4300 4194560 1 0 0 0 0 0 0 0 20 0 1 0 987654 0 0";
+        let stat = parse_stat(data).unwrap();
+        assert_eq!(stat.pid, 4242);
+        assert_eq!(stat.comm, "tmux: server (1)");
+        assert_eq!(stat.ppid, 4200);
+        assert_eq!(stat.pgrp, 4242);
+        assert_eq!(stat.tty_nr, 34817);
+        assert_eq!(stat.tpgid, 4300);
+        assert_eq!(stat.start_time, 987654);
+    }
+
+    #[test]
+    fn test_own_process() {
+        let pid = std::process::id() as i32;
+        assert_eq!(stat(pid).unwrap().pid, pid);
+        assert_eq!(cwd(pid), std::env::current_dir().ok());
+        assert!(cmdline(pid).is_some());
+        assert_eq!(environ(pid).get("PATH").cloned(), std::env::var("PATH").ok());
+    }
+}
@@ -4,17 +4,26 @@ use hyprland::event_listener::EventListener;
 use hyprland::prelude::*;
 use hyprland::shared::Address;
 use std::fs::File;
-use std::io::{read_to_string, Write};
-use crate::command_detection::fetch_command;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+use crate::command_detection::{fetch_command, DetectionCache};
+use crate::dbus::ServiceIndex;
+use crate::config::{Config, MultiWindowStrategy};
+use crate::launch::{launch_entries, LaunchReport};
+use crate::suggest::UnresolvedClient;
+use crate::{process, shell};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 const EXEC_NAME: &str = "exec.conf";
 const CLIENTS_PATH_NAME: &str = "clients.json";
+const ENTRIES_PATH_NAME: &str = "entries.json";
+const UNRESOLVED_PATH_NAME: &str = "unresolved.json";
 
 pub trait Session {
     fn save(&self, name: &str) -> hyprland::Result<()>;
-    fn load(&self, name: &str) -> hyprland::Result<()>;
+    fn load(&self, name: &str) -> hyprland::Result<LaunchReport>;
     fn clear(&self) -> hyprland::Result<()>;
     fn list(&self) -> impl Iterator<Item=String>;
     fn delete(&self, name: &str);
@@ -44,7 +53,194 @@ pub struct LocalSession {
     pub simulate: bool,
     pub load_time:  u64, 
     pub adjust_clients_only: bool,
-    pub save_duplicate_pids: bool
+    pub save_duplicate_pids: bool,
+    pub launch_retries: u32,
+    pub retry_delay: u64,
+    pub config: Config,
+    /// Detection results reused by later saves
+    pub detection: DetectionCache,
+}
+
+impl Default for LocalSession {
+    fn default() -> Self {
+        LocalSession {
+            base_path: String::new(),
+            simulate: false,
+            load_time: 60,
+            adjust_clients_only: false,
+            save_duplicate_pids: false,
+            launch_retries: 2,
+            retry_delay: 10,
+            config: Config::default(),
+            detection: DetectionCache::default(),
+        }
+    }
+}
+
+/// A program launched when restoring a session along with the client it is expected to recreate
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionEntry {
+    /// Program and arguments exactly as they are passed to the program
+    #[serde(alias = "command", deserialize_with = "argv_or_command_line")]
+    pub argv: Vec<String>,
+    pub client: Client,
+    /// Further windows owned by the same process, in the order they were saved
+    #[serde(default)]
+    pub other_windows: Vec<Client>,
+    /// Directory the program was running in
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Environment variables from the config's allowlist that differed from Hyprsession's own
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Command relaunched for the further windows when it differs from the first window's, as for terminals
+    #[serde(default)]
+    pub window_argv: Option<Vec<String>>,
+}
+
+/// The directory a program is relaunched in, leaving out the home directory it starts in anyway
+/// and directories deleted while it ran
+fn saved_cwd(dir: &str) -> Option<String> {
+    let home = std::env::var("HOME").unwrap_or_default();
+    (dir != home && dir != "/" && !dir.ends_with(" (deleted)")).then(|| dir.to_string())
+}
+
+/// Commands of entries saved by earlier versions are a single command line
+fn argv_or_command_line<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Command {
+        Argv(Vec<String>),
+        Line(String),
+    }
+    Ok(match Command::deserialize(deserializer)? {
+        Command::Argv(argv) => argv,
+        Command::Line(line) => shell::split(&line)
+            .unwrap_or_else(|| line.split_whitespace().map(String::from).collect()),
+    })
+}
+
+/// A launch needed to restore one window of an entry
+pub struct WindowLaunch<'a> {
+    pub client: &'a Client,
+    /// Line to dispatch, or None when the window isn't launched on its own
+    pub exec_line: Option<String>,
+    /// Whether the application is expected to restore the window itself, so a window that doesn't appear isn't a failure
+    pub by_application: bool,
+}
+
+impl SessionEntry {
+    /// Record the command along with the directory and environment of the client's process
+    pub fn new(argv: Vec<String>, client: &Client, config: &Config) -> Self {
+        let cwd = process::cwd(client.pid).and_then(|dir| saved_cwd(&dir.to_string_lossy()));
+        let environ = process::environ(client.pid);
+        let env = config.environment
+            .iter()
+            .filter_map(|name| {
+                let value = environ.get(name)?;
+                (std::env::var(name).ok().as_ref() != Some(value)).then(|| (name.clone(), value.clone()))
+            })
+            .collect();
+
+        SessionEntry { argv, client: client.clone(), other_windows: vec![], cwd, env, window_argv: None }
+    }
+
+    /// The program's name, used to look up how it is restored
+    pub fn program(&self) -> &str {
+        self.argv.first().map_or("", String::as_str)
+    }
+
+    /// The command as a shell command line
+    pub fn command_line(&self) -> String {
+        shell::join(&self.argv)
+    }
+
+    /// The `[rules] command` line passed to `dispatch exec`
+    pub fn exec_line(&self) -> String {
+        self.exec_line_for(&self.client, &self.argv)
+    }
+
+    fn exec_line_for(&self, client: &Client, argv: &[String]) -> String {
+        format!("[{}] {}", client_rules(client).join(";"), self.with_context(argv))
+    }
+
+    /// A command line running a command in the recorded directory, if it still exists, and environment.
+    /// The line never spans several lines, as exec files and Hyprland's config are read line by line.
+    fn with_context(&self, argv: &[String]) -> String {
+        let mut line = String::new();
+        let mut values = argv.iter().chain(self.cwd.iter()).chain(self.env.values());
+        if values.any(|value| value.contains('\n')) {
+            line += shell::NEWLINE_SETUP;
+        }
+        if self.cwd.is_none() && self.env.is_empty() {
+            return line + &shell::join_line(argv);
+        }
+
+        if let Some(cwd) = &self.cwd {
+            line += &format!("cd {} 2>/dev/null; ", shell::quote_line(cwd));
+        }
+        for (name, value) in self.env.iter() {
+            line += &format!("{}={} ", name, shell::quote_line(value));
+        }
+        line + "exec " + &shell::join_line(argv)
+    }
+
+    /// The launches needed to restore every window of the entry
+    pub fn window_launches(&self, strategy: &MultiWindowStrategy) -> Vec<WindowLaunch<'_>> {
+        let mut launches = vec![WindowLaunch { client: &self.client, exec_line: Some(self.exec_line()), by_application: false }];
+        for window in self.other_windows.iter() {
+            let exec_line = match strategy {
+                MultiWindowStrategy::NewWindow(flag) => {
+                    // Browsers relaunched into a profile already open a new window
+                    let has_flag = self.argv.contains(flag);
+                    let argv: Vec<String> = self.argv.iter().chain(Some(flag).filter(|_| !has_flag)).cloned().collect();
+                    Some(self.exec_line_for(window, &argv))
+                }
+                MultiWindowStrategy::Relaunch => Some(self.exec_line_for(window, self.window_argv.as_ref().unwrap_or(&self.argv))),
+                MultiWindowStrategy::SelfRestore | MultiWindowStrategy::Once => None,
+            };
+            let by_application = *strategy == MultiWindowStrategy::SelfRestore;
+            launches.push(WindowLaunch { client: window, exec_line, by_application });
+        }
+        launches
+    }
+}
+
+/// Matches windows opening during a load to saved clients of the same class in order of appearance
+struct WindowMatcher {
+    clients: Vec<Client>,
+    matched: HashMap<Address, usize>,
+}
+
+impl WindowMatcher {
+    fn new(clients: Vec<Client>) -> Self {
+        WindowMatcher { clients, matched: HashMap::new() }
+    }
+
+    fn is_unmatched(&self, index: usize) -> bool {
+        !self.matched.values().any(|&i| i == index)
+    }
+
+    /// Match a new window to the first unmatched saved client of the same class
+    fn match_opened(&mut self, real_client: &Client) -> Option<&Client> {
+        let index = (0..self.clients.len()).find(|&i| {
+            self.is_unmatched(i)
+                && self.clients[i].initial_class.eq_ignore_ascii_case(&real_client.initial_class)
+        })?;
+        self.matched.insert(real_client.address.clone(), index);
+        Some(&self.clients[index])
+    }
+
+    /// Rematch a window whose title now identifies an unmatched saved client of the same class
+    fn match_title(&mut self, real_client: &Client) -> Option<&Client> {
+        let index = (0..self.clients.len()).find(|&i| {
+            self.is_unmatched(i)
+                && self.clients[i].initial_class.eq_ignore_ascii_case(&real_client.initial_class)
+                && self.clients[i].title == real_client.title
+        })?;
+        self.matched.insert(real_client.address.clone(), index);
+        Some(&self.clients[index])
+    }
 }
 
 struct ClientAdjustmentContext<'a> {
@@ -83,8 +279,8 @@ impl CheckAndAdjust for ClientAdjustmentContext<'_> {
 
 fn adjust_client(real_client: &Client, session_client: &Client, simulate: bool) {
     let context = ClientAdjustmentContext {
-        real_client: real_client,
-        session_client: session_client,
+        real_client,
+        session_client,
         simulate,
     };
 
@@ -159,10 +355,7 @@ fn adjust_client(real_client: &Client, session_client: &Client, simulate: bool)
     }
 }
 
-fn process_window_event(address: Address, clients_data: &'static str, start_time: std::time::Instant, load_time: u64, simulate: bool) {
-    let clients: Vec<Client> = serde_json::from_str(clients_data)
-        .expect("Failed to parse clients data");
-
+fn process_window_event(address: Address, opened: bool, matcher: &Mutex<WindowMatcher>, start_time: std::time::Instant, load_time: u64, simulate: bool) {
     if start_time.elapsed().as_secs() > load_time { 
         println!("Load time exceeded, skipping client adjustments");
         return;
@@ -171,122 +364,215 @@ fn process_window_event(address: Address, clients_data: &'static str, start_time
     let real_clients = Clients::get()
         .expect("Unable to fetch clients");
     if let Some(real_client) = real_clients.iter().find(|c| c.address == address) {
-        for session_client in clients.iter() {
-            if session_client.title == real_client.title {
+        let mut matcher = matcher.lock().unwrap();
+        let session_client = iif!(opened, matcher.match_opened(real_client), matcher.match_title(real_client));
+        match session_client {
+            Some(session_client) => {
                 println!("Adjusting client: {:?}", real_client.title);
                 adjust_client(real_client, session_client, simulate);
-                break;
-            } else {
-                println!("Client '{:?}' not found - skipping", address);
             }
+            None if opened => println!("Client '{:?}' not found - skipping", address),
+            None => {}
         }
     }
 }
 
-fn load_programs(base_path: &PathBuf, simulate: bool) -> hyprland::Result<()> {
-    let session_file = File::open(base_path.join(EXEC_NAME));
+/// Hyprland rules that place a window the way the given client was placed.
+/// Used both for exec rules and for exported window rules.
+pub fn client_rules(info: &Client) -> Vec<String> {
+    let props = [
+        |info: &Client| format!("monitor {:?}", info.monitor.unwrap_or(0)),
+        |info: &Client| iif!(info.workspace.id == -99,
+                            "workspace special silent".to_string(),
+                            format!("workspace {} silent", info.workspace.id)),
+        |info: &Client| iif!(info.floating, "float").to_string(),
+        |info: &Client| format!("move {} {}", info.at.0, info.at.1),
+        |info: &Client| format!("size {} {}", info.size.0, info.size.1),
+        |info: &Client| iif!(info.pinned, "pin").to_string(),
+        |info: &Client| format!("fullscreenstate {}", info.fullscreen as i32),
+    ];
+
+    props
+        .iter()
+        .map(|opt| opt(info))
+        .filter(|opt| !opt.is_empty())
+        .collect()
+}
 
-    if session_file.is_ok() {
-        for line in read_to_string(session_file.unwrap()).unwrap().lines() {
-            if !simulate {
-                hyprland::dispatch!(Exec, line)?;
+/// Write the exec line of every window to launch, one per line
+fn write_exec_file(path: &Path, entries: &[SessionEntry], config: &Config) -> std::io::Result<()> {
+    let mut exec_file = File::create(path)?;
+    for entry in entries.iter() {
+        let strategy = config.multi_window_strategy(entry.program());
+        for launch in entry.window_launches(&strategy) {
+            if let Some(line) = launch.exec_line {
+                exec_file.write_all(format!("{}\n", line).as_bytes())?;
             }
-            println!("Sending: dispatch exec {line}");
         }
     }
-
     Ok(())
 }
 
+fn read_exec_lines(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .map(|data| data.lines().filter(|line| !line.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn read_entries(base_path: &Path) -> Option<Vec<SessionEntry>> {
+    let data = std::fs::read_to_string(base_path.join(ENTRIES_PATH_NAME)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+fn load_programs(session: &LocalSession, base_path: &Path) -> hyprland::Result<LaunchReport> {
+    let simulate = session.simulate;
+    // Sessions saved before entries were recorded can only be launched blindly
+    if let (Some(entries), false) = (read_entries(base_path), simulate) {
+        return launch_entries(&entries, &session.config, session.launch_retries, session.retry_delay);
+    }
+
+    for line in read_exec_lines(&base_path.join(EXEC_NAME)) {
+        if !simulate {
+            hyprland::dispatch!(Exec, &line)?;
+        }
+        println!("Sending: dispatch exec {line}");
+    }
+
+    Ok(LaunchReport::default())
+}
+
+impl LocalSession {
+    /// Clients recorded when the session was last saved
+    pub fn saved_clients(&self, name: &str) -> Vec<Client> {
+        let base_dir: PathBuf = to_base_dir!(self.base_path.clone(), name.to_string());
+        std::fs::read_to_string(base_dir.join(CLIENTS_PATH_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Windows that were saved with their raw cmdline the last time the session was saved
+    pub fn unresolved_clients(&self, name: &str) -> Vec<UnresolvedClient> {
+        let base_dir: PathBuf = to_base_dir!(self.base_path.clone(), name.to_string());
+        std::fs::read_to_string(base_dir.join(UNRESOLVED_PATH_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Lines of the session's exec file, each a `[rules] command` pair for `dispatch exec`
+    pub fn exec_lines(&self, name: &str) -> Vec<String> {
+        let base_dir: PathBuf = to_base_dir!(self.base_path.clone(), name.to_string());
+        read_exec_lines(&base_dir.join(EXEC_NAME))
+    }
+}
+
 impl Session for LocalSession {
     fn save(&self, name: &str) -> hyprland::Result<()> {
         println!("Saving session: {}", name);
         let base_dir: PathBuf = to_base_dir!(self.base_path.clone(), name.to_string());
         std::fs::create_dir_all(&base_dir).expect("Failed to create session directory");
 
-        let props = [
-            |info: &Client| format!("monitor {:?}", info.monitor.unwrap_or(0)),
-            |info: &Client| iif!(info.workspace.id == -99,
-                                format!("workspace special silent"),
-                                format!("workspace {} silent", info.workspace.id)),
-            |info: &Client| format!("{}", iif!(info.floating, "float")),
-            |info: &Client| format!("move {} {}", info.at.0, info.at.1),
-            |info: &Client| format!("size {} {}", info.size.0, info.size.1),
-            |info: &Client| format!("{}", iif!(info.pinned, "pin")),
-            |info: &Client| format!("fullscreenstate {}", info.fullscreen as i32),
-        ];
-
         let client_info = Clients::get().expect("Unable to fetch clients");
 
-        let mut exec_file = File::create(base_dir.join(EXEC_NAME))
-            .expect("Failed to create session file");
         let clients_file = File::create(base_dir.join(CLIENTS_PATH_NAME))
             .expect("Failed to create clients file");
+        let entries_file = File::create(base_dir.join(ENTRIES_PATH_NAME))
+            .expect("Failed to create entries file");
         let mut pids: Vec<i32> = vec![];
         let mut saved_clients: Vec<Client> = vec![];
-        let xdg_map: HashMap<String, String> = crate::command_faker::build_xdg_command_map();
+        let mut entries: Vec<SessionEntry> = vec![];
+        let mut unresolved: Vec<UnresolvedClient> = vec![];
+        let desktop = self.detection.desktop.index();
+        let services = ServiceIndex::load();
 
         for info in client_info.iter().rev() {
             saved_clients.push(info.clone());
             if !self.save_duplicate_pids && pids.contains(&info.pid) {
+                if let Some(entry) = entries.iter_mut().find(|entry| entry.client.pid == info.pid) {
+                    entry.other_windows.push(info.clone());
+                }
                 continue;
             }
-            let cmd = fetch_command(info, &xdg_map);
-            if !cmd.is_ok() {
+            let Ok(detected) = fetch_command(info, &desktop, &services, &self.detection, &self.config) else {
                 continue;
-            }
+            };
             pids.push(info.pid);
+            if !detected.resolved {
+                unresolved.push(UnresolvedClient::new(info, &detected.argv));
+            }
 
-            let exec_opts: Vec<String> = props
-                .iter()
-                .map(|opt| opt(info))
-                .filter(|opt| !opt.is_empty())
-                .collect();
-            exec_file
-                .write_all(format!("[{}] {}\n", exec_opts.join(";"), cmd.unwrap()).as_bytes())?;
+            let mut entry = SessionEntry::new(detected.argv, info, &self.config);
+            // Desktop entries name the directory their program has to be started in
+            if detected.cwd.is_some() {
+                entry.cwd = detected.cwd;
+            }
+            entry.window_argv = detected.window_argv;
+            entries.push(entry);
         }
 
+        self.detection.retain(&pids);
+
+        write_exec_file(&base_dir.join(EXEC_NAME), &entries, &self.config)?;
+
         serde_json::to_writer(&clients_file, &saved_clients)
             .expect("Failed to write to clients file");
+        serde_json::to_writer(&entries_file, &entries)
+            .expect("Failed to write to entries file");
+        std::fs::write(base_dir.join(UNRESOLVED_PATH_NAME), serde_json::to_string(&unresolved)?)?;
+        if !unresolved.is_empty() {
+            println!(
+                "{} window(s) were saved with their raw command line, run `hyprsession command {} --suggest` for suggestions",
+                unresolved.len(), name
+            );
+        }
         println!("Session saved");
         Ok(())
     }
 
-    fn load(&self, name: &str) -> hyprland::Result<()> {
+    fn load(&self, name: &str) -> hyprland::Result<LaunchReport> {
         println!("Loading session: {}", name);
         let base_dir: PathBuf = to_base_dir!(self.base_path.clone(), name.to_string());
         let start_time = std::time::Instant::now();
 
         if !self.adjust_clients_only {
             self.clear()?;
-            load_programs(&base_dir.clone(), self.simulate)?;
         }
 
         // Clone the values we need inside the thread before moving into the closure
         let load_time = self.load_time;
         let simulate = self.simulate;
-        
-        std::thread::spawn(move || {
-            let clients_file_path = base_dir.join(CLIENTS_PATH_NAME);
-            let clients_data = std::fs::read_to_string(&clients_file_path)
-                .unwrap_or_else(|_| "[]".to_string());
-            let clients_data: &'static str = Box::leak(clients_data.into_boxed_str());
+        let matcher = Arc::new(Mutex::new(WindowMatcher::new(self.saved_clients(name))));
 
+        // Start listening before launching so windows that open while launches are verified still get adjusted
+        std::thread::spawn(move || {
+            let title_matcher = matcher.clone();
             let mut event_listener = EventListener::new();
             event_listener.add_window_title_changed_handler({move |event| {
                 println!("Window title changed: {}", event.address);
-                process_window_event(event.address, clients_data, start_time, load_time, simulate);
+                process_window_event(event.address, false, &title_matcher, start_time, load_time, simulate);
             }});
             event_listener.add_window_opened_handler({move |event| {
                 println!("Window opened: {}", event.window_address);
-                process_window_event(event.window_address, clients_data, start_time, load_time, simulate);
+                process_window_event(event.window_address, true, &matcher, start_time, load_time, simulate);
             }});
             let _ = event_listener.start_listener();
         });
 
-        std::thread::sleep(std::time::Duration::from_secs(self.load_time + 1));
+        let mut report = LaunchReport::default();
+        if !self.adjust_clients_only {
+            report = load_programs(self, &base_dir)?;
+        }
+
+        let load_duration = std::time::Duration::from_secs(self.load_time + 1);
+        std::thread::sleep(load_duration.saturating_sub(start_time.elapsed()));
+
+        if !self.simulate && !self.adjust_clients_only && read_entries(&base_dir).is_some() {
+            report.check_placement()?;
+            report.print_summary();
+        }
         println!("Finished loading session");
-        Ok(())
+        Ok(report)
     }
 
     fn clear(&self) -> hyprland::Result<()> {
@@ -335,4 +621,188 @@ impl Session for LocalSession {
             println!("Failed to delete session: {}", name);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn test_client(address: &str, class: &str, title: &str, workspace: i32) -> Client {
+        serde_json::from_value(serde_json::json!({
+            "address": address, "at": [10, 20], "size": [800, 600],
+            "workspace": { "id": workspace, "name": workspace.to_string() },
+            "floating": true, "fullscreen": 0, "fullscreenClient": 0, "monitor": 0,
+            "initialClass": class, "class": class, "initialTitle": title, "title": title,
+            "pid": 1, "xwayland": false, "pinned": false, "grouped": [], "mapped": true,
+            "swallowing": null, "focusHistoryID": 0
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_window_launches() {
+        let entry = SessionEntry {
+            argv: vec!["firefox".to_string()],
+            client: test_client("0x1", "firefox", "", 1),
+            other_windows: vec![test_client("0x2", "firefox", "", 2)],
+            cwd: None,
+            env: BTreeMap::new(),
+            window_argv: None,
+        };
+
+        let launches = entry.window_launches(&MultiWindowStrategy::NewWindow("--new-window".to_string()));
+        assert_eq!(launches.len(), 2);
+        assert!(launches[0].exec_line.as_ref().unwrap().ends_with("] firefox"));
+        assert!(launches[1].exec_line.as_ref().unwrap().contains("workspace 2 silent"));
+        assert!(launches[1].exec_line.as_ref().unwrap().ends_with("] firefox --new-window"));
+
+        let launches = entry.window_launches(&MultiWindowStrategy::SelfRestore);
+        assert_eq!(launches.len(), 2);
+        assert!(launches[1].exec_line.is_none());
+        assert!(launches[1].by_application);
+
+        let launches = entry.window_launches(&MultiWindowStrategy::Once);
+        assert!(launches[1].exec_line.is_none());
+        assert!(!launches[1].by_application);
+
+        // Further terminal windows are opened without the directory and program of the first
+        let terminal = SessionEntry {
+            argv: ["kitty", "--directory", "/src", "htop"].iter().map(|arg| arg.to_string()).collect(),
+            client: test_client("0x1", "kitty", "", 1),
+            other_windows: vec![test_client("0x2", "kitty", "", 2)],
+            cwd: None,
+            env: BTreeMap::new(),
+            window_argv: Some(vec!["kitty".to_string()]),
+        };
+        let launches = terminal.window_launches(&MultiWindowStrategy::Relaunch);
+        assert!(launches[0].exec_line.as_ref().unwrap().ends_with("] kitty --directory /src htop"));
+        assert!(launches[1].exec_line.as_ref().unwrap().ends_with("] kitty"));
+    }
+
+    #[test]
+    fn test_exec_line_context() {
+        let mut entry = SessionEntry {
+            argv: vec!["mpv".to_string(), "./clip.mp4".to_string()],
+            client: test_client("0x1", "mpv", "", 1),
+            other_windows: vec![],
+            cwd: Some("/home/user/My Videos".to_string()),
+            env: BTreeMap::from([("VIRTUAL_ENV".to_string(), "/home/user/.venv".to_string())]),
+            window_argv: None,
+        };
+        assert!(entry.exec_line().ends_with("] cd '/home/user/My Videos' 2>/dev/null; VIRTUAL_ENV=/home/user/.venv exec mpv ./clip.mp4"));
+
+        entry.cwd = None;
+        entry.env.clear();
+        assert!(entry.exec_line().ends_with("] mpv ./clip.mp4"));
+    }
+
+    #[test]
+    fn test_missing_cwd() {
+        assert_eq!(saved_cwd("/home/user/project").unwrap(), "/home/user/project");
+        assert!(saved_cwd("/home/user/old build (deleted)").is_none());
+        assert!(saved_cwd("/").is_none());
+
+        // A directory that is gone by the time the session is restored doesn't stop the program from starting
+        let entry = SessionEntry {
+            argv: vec!["echo".to_string(), "started".to_string()],
+            client: test_client("0x1", "echo", "", 1),
+            other_windows: vec![],
+            cwd: Some("/tmp/hyprsession-removed-directory".to_string()),
+            env: BTreeMap::new(),
+            window_argv: None,
+        };
+        let line = entry.exec_line();
+        let (_, command) = line.split_once("] ").unwrap();
+        let output = std::process::Command::new("sh").args(["-c", command]).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "started\n");
+        assert!(output.stderr.is_empty());
+    }
+
+    #[test]
+    fn test_exec_line_round_trip() {
+        let args = ["My Clip; rm -rf ~.mp4", "--title=it's [1]", "$HOME", "#1", "a\nb", "", "end\n"];
+        let argv: Vec<String> = ["printf", "%s\\0"].iter().chain(args.iter()).map(|arg| arg.to_string()).collect();
+        let entry = SessionEntry {
+            argv: argv.clone(),
+            client: test_client("0x1", "mpv", "", 1),
+            other_windows: vec![],
+            cwd: Some("/tmp/a ] b".to_string()),
+            env: BTreeMap::from([("NOTE".to_string(), "two\nlines".to_string())]),
+            window_argv: None,
+        };
+
+        // The rules end at the first closing bracket so everything after it must be a plain shell line
+        let line = entry.exec_line();
+        let (rules, command) = line.split_once("] ").unwrap();
+        assert_eq!(rules, format!("[{}", client_rules(&entry.client).join(";")));
+        assert!(!line.contains('\n'));
+        assert_eq!(run_line(&line), args);
+
+        let json = serde_json::to_string(&entry).unwrap();
+        let restored: SessionEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.argv, argv);
+        assert!(command.starts_with(shell::NEWLINE_SETUP));
+    }
+
+    /// Run the command of an exec line, returning the arguments it printed separated by null bytes
+    fn run_line(line: &str) -> Vec<String> {
+        let (_, command) = line.split_once("] ").unwrap();
+        let output = std::process::Command::new("sh").args(["-c", command]).output().unwrap();
+        String::from_utf8(output.stdout).unwrap().split_terminator('\0').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_exec_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EXEC_NAME);
+        let entries = vec![
+            SessionEntry {
+                argv: ["printf", "%s\\0", "first\nsecond"].iter().map(|arg| arg.to_string()).collect(),
+                client: test_client("0x1", "mpv", "", 1),
+                other_windows: vec![],
+                cwd: None,
+                env: BTreeMap::new(),
+                window_argv: None,
+            },
+            SessionEntry {
+                argv: vec!["kitty".to_string()],
+                client: test_client("0x2", "kitty", "", 2),
+                other_windows: vec![],
+                cwd: None,
+                env: BTreeMap::new(),
+                window_argv: None,
+            },
+        ];
+
+        write_exec_file(&path, &entries, &Config::default()).unwrap();
+        let lines = read_exec_lines(&path);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(run_line(&lines[0]), vec!["first\nsecond"]);
+        assert!(lines[1].ends_with("] kitty"));
+    }
+
+    #[test]
+    fn test_load_old_entries() {
+        let client = serde_json::to_value(test_client("0x1", "kitty", "", 1)).unwrap();
+        let old = serde_json::json!([{ "command": "kitty --directory '/tmp/My Dir'", "client": client }]);
+        let entries: Vec<SessionEntry> = serde_json::from_value(old).unwrap();
+        assert_eq!(entries[0].argv, vec!["kitty", "--directory", "/tmp/My Dir"]);
+        assert!(entries[0].other_windows.is_empty());
+        assert!(entries[0].cwd.is_none());
+    }
+
+    #[test]
+    fn test_window_matcher() {
+        let mut matcher = WindowMatcher::new(vec![
+            test_client("0x1", "firefox", "One", 1),
+            test_client("0x2", "firefox", "Two", 2),
+            test_client("0x3", "kitty", "", 3),
+        ]);
+
+        let first = test_client("0xa", "firefox", "Mozilla Firefox", 5);
+        let second = test_client("0xb", "firefox", "Mozilla Firefox", 5);
+        assert_eq!(matcher.match_opened(&first).unwrap().workspace.id, 1);
+        assert_eq!(matcher.match_opened(&second).unwrap().workspace.id, 2);
+        assert!(matcher.match_opened(&test_client("0xc", "firefox", "", 5)).is_none());
+        assert_eq!(matcher.match_opened(&test_client("0xd", "kitty", "", 5)).unwrap().workspace.id, 3);
+    }
+}
@@ -0,0 +1,139 @@
+/// Quote a string so a POSIX shell treats it as a single word
+pub fn quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Join arguments into a command line a POSIX shell splits back into the same arguments
+pub fn join<S: AsRef<str>>(args: &[S]) -> String {
+    args.iter().map(|arg| quote(arg.as_ref())).collect::<Vec<String>>().join(" ")
+}
+
+/// Sets `$nl` to a newline for the words `quote_line` writes, as command substitution drops trailing newlines
+pub const NEWLINE_SETUP: &str = "nl=\"$(printf '\\n.')\"; nl=\"${nl%.}\"; ";
+
+/// Quote a string as a single word that fits on one line, writing newlines as `$nl` which has to be
+/// set with `NEWLINE_SETUP` first
+pub fn quote_line(arg: &str) -> String {
+    if !arg.contains('\n') {
+        return quote(arg);
+    }
+    arg.split('\n').map(quote).collect::<Vec<String>>().join("\"$nl\"")
+}
+
+/// Join arguments into a command line like `join`, keeping it on one line with `quote_line`
+pub fn join_line<S: AsRef<str>>(args: &[S]) -> String {
+    args.iter().map(|arg| quote_line(arg.as_ref())).collect::<Vec<String>>().join(" ")
+}
+
+/// Split a command line into arguments the way a POSIX shell does for a single simple command.
+/// Returns None if the quoting is unbalanced.
+pub fn split(line: &str) -> Option<Vec<String>> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            escaped @ ('$' | '`' | '"' | '\\') => word.push(escaped),
+                            '\n' => {}
+                            other => word.extend(['\\', other]),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(escaped) => arg.get_or_insert_with(String::new).push(escaped),
+                None => arg.get_or_insert_with(String::new).push('\\'),
+            },
+            ' ' | '\t' | '\n' => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Some(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("/usr/bin/firefox"), "/usr/bin/firefox");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("mpv  'My Clip.mp4' \"a \\\"b\\\"\" c\\ d").unwrap(), vec!["mpv", "My Clip.mp4", "a \"b\"", "c d"]);
+        assert_eq!(split("app '' x").unwrap(), vec!["app", "", "x"]);
+        assert!(split("app 'unterminated").is_none());
+        assert!(split("app \"unterminated").is_none());
+    }
+
+    #[test]
+    fn test_join_round_trip() {
+        let hostile = [
+            "prog", "", " ", "two words", "semi;colon", "it's", "\"double\"", "back\\slash", "$HOME", "`id`",
+            "$(rm -rf ~)", "a && b", "pipe|line", "[workspace 1]", "#comment", "new\nline", "tab\there", "*.txt",
+            "~", "ünïcödé", "--flag=a b", "'", "\\'",
+        ];
+        let line = join(&hostile);
+        assert_eq!(split(&line).unwrap(), hostile);
+
+        // The line is also understood the same way by a real shell
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("for arg in {}; do printf '%s\\0' \"$arg\"; done", join(&hostile[1..])))
+            .output()
+            .unwrap();
+        let printed: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect();
+        assert_eq!(printed, hostile[1..]);
+    }
+
+    #[test]
+    fn test_join_line() {
+        let args = ["new\nline", "\n", "trailing\n\n", "it's\n$HOME", "plain"];
+        let line = join_line(&args);
+        assert!(!line.contains('\n'));
+        assert_eq!(join_line(&["plain", "two words"]), join(&["plain", "two words"]));
+
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{}for arg in {}; do printf '%s\\0' \"$arg\"; done", NEWLINE_SETUP, line))
+            .output()
+            .unwrap();
+        let printed: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect();
+        assert_eq!(printed, args);
+    }
+}
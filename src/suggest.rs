@@ -0,0 +1,167 @@
+use hyprland::data::Client;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use crate::command_detection::executable_name;
+use crate::desktop_entry::{DesktopEntry, DesktopIndex};
+
+/// A window saved with its raw cmdline because its program couldn't be found in PATH, a desktop entry or the alias table
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnresolvedClient {
+    pub class: String,
+    pub initial_class: String,
+    pub initial_title: String,
+    /// The command the window was saved with
+    pub command: Vec<String>,
+}
+
+impl UnresolvedClient {
+    pub fn new(client: &Client, argv: &[String]) -> UnresolvedClient {
+        UnresolvedClient {
+            class: client.class.clone(),
+            initial_class: client.initial_class.clone(),
+            initial_title: client.initial_title.clone(),
+            command: argv.to_vec(),
+        }
+    }
+}
+
+/// Package managers that export desktop files for the applications they install
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Package {
+    Flatpak,
+    Snap,
+}
+
+impl Package {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Package::Flatpak => "flatpak",
+            Package::Snap => "snap",
+        }
+    }
+}
+
+/// A command found for an unresolved window in the desktop files of an installed package
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub package: Package,
+    /// Id of the desktop entry the command was taken from
+    pub desktop_entry: String,
+    pub command: Vec<String>,
+}
+
+/// Directories flatpak and snapd export desktop files to, whether or not they are in XDG_DATA_DIRS
+fn package_desktop_dirs() -> Vec<(Package, PathBuf)> {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    vec![
+        (Package::Flatpak, home.join(".local/share/flatpak/exports/share/applications")),
+        (Package::Flatpak, PathBuf::from("/var/lib/flatpak/exports/share/applications")),
+        (Package::Snap, PathBuf::from("/var/lib/snapd/desktop/applications")),
+    ]
+}
+
+/// The command running an exported desktop entry through its package manager
+fn package_command(package: Package, entry: &DesktopEntry) -> Vec<String> {
+    let run = |name: String| vec![String::from(package.name()), String::from("run"), name];
+    match package {
+        // Exec lines look like `/usr/bin/flatpak run --branch=stable --command=app org.app.Id @@u %u @@`
+        Package::Flatpak => {
+            let mut args = entry.exec.iter().skip_while(|arg| *arg != "run").skip(1);
+            run(args.find(|arg| !arg.starts_with('-')).cloned().unwrap_or_else(|| entry.id.clone()))
+        }
+        // Exec lines run `/snap/bin/<snap>.<app>`, desktop ids are `<snap>_<app>`
+        Package::Snap => match entry.exec.iter().find_map(|arg| arg.strip_prefix("/snap/bin/")) {
+            Some(name) => run(name.to_string()),
+            None => {
+                let (snap, app) = entry.id.split_once('_').unwrap_or((&entry.id, &entry.id));
+                run(if snap == app { snap.to_string() } else { format!("{}.{}", snap, app) })
+            }
+        },
+    }
+}
+
+/// The entry of a package for a window, by window class, application id or executable name.
+/// Application ids are also matched by their last part, e.g. `firefox` for `org.mozilla.firefox`.
+fn find_entry<'a>(index: &'a DesktopIndex, unresolved: &UnresolvedClient) -> Option<&'a DesktopEntry> {
+    let program = unresolved.command.first().map(|program| executable_name(program)).unwrap_or_default();
+    let keys: Vec<&str> = [unresolved.initial_class.as_str(), unresolved.class.as_str(), program]
+        .into_iter()
+        .filter(|key| !key.is_empty())
+        .collect();
+
+    keys.iter().find_map(|key| index.find(key)).or_else(|| {
+        index.entries().iter().find(|entry| {
+            let id = entry.id.split('_').next().unwrap_or(&entry.id);
+            let short = id.rsplit('.').next().unwrap_or(id);
+            keys.iter().any(|key| short.eq_ignore_ascii_case(key))
+        })
+    })
+}
+
+fn suggest_from(indexes: &[(Package, DesktopIndex)], unresolved: &UnresolvedClient) -> Option<Suggestion> {
+    indexes.iter().find_map(|(package, index)| {
+        find_entry(index, unresolved).map(|entry| Suggestion {
+            package: *package,
+            desktop_entry: entry.id.clone(),
+            command: package_command(*package, entry),
+        })
+    })
+}
+
+/// Suggest commands for unresolved windows from the desktop files of installed flatpaks and snaps
+pub fn suggest_commands(unresolved: &[UnresolvedClient]) -> Vec<Option<Suggestion>> {
+    let indexes: Vec<(Package, DesktopIndex)> = package_desktop_dirs()
+        .into_iter()
+        .map(|(package, dir)| (package, DesktopIndex::load_from(&[dir])))
+        .filter(|(_, index)| !index.is_empty())
+        .collect();
+    unresolved.iter().map(|client| suggest_from(&indexes, client)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unresolved(class: &str, command: &[&str]) -> UnresolvedClient {
+        UnresolvedClient {
+            class: class.to_string(),
+            initial_class: class.to_string(),
+            initial_title: String::new(),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn index(entries: &[(&str, &str)]) -> DesktopIndex {
+        DesktopIndex::new(entries.iter().map(|(id, content)| DesktopEntry::parse(id, content).unwrap()).collect())
+    }
+
+    #[test]
+    fn test_suggest() {
+        let flatpak = index(&[
+            ("org.mozilla.firefox", "[Desktop Entry]\nType=Application\nName=Firefox\nStartupWMClass=firefox\n\
+                Exec=/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox --file-forwarding org.mozilla.firefox @@u %u @@\n"),
+            ("com.spotify.Client", "[Desktop Entry]\nType=Application\nName=Spotify\n\
+                Exec=/usr/bin/flatpak run --branch=stable --command=spotify com.spotify.Client %U\n"),
+        ]);
+        let snap = index(&[
+            ("telegram-desktop_telegram-desktop", "[Desktop Entry]\nType=Application\nName=Telegram\n\
+                Exec=env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/telegram-desktop_telegram-desktop.desktop /snap/bin/telegram-desktop -- %u\n"),
+        ]);
+        let indexes = vec![(Package::Flatpak, flatpak), (Package::Snap, snap)];
+
+        let firefox = suggest_from(&indexes, &unresolved("firefox", &["/app/lib/firefox/firefox"])).unwrap();
+        assert_eq!(firefox.package, Package::Flatpak);
+        assert_eq!(firefox.desktop_entry, "org.mozilla.firefox");
+        assert_eq!(firefox.command, vec!["flatpak", "run", "org.mozilla.firefox"]);
+
+        // Matched by the last part of the application id
+        let spotify = suggest_from(&indexes, &unresolved("Spotify", &["/app/extra/spotify"])).unwrap();
+        assert_eq!(spotify.command, vec!["flatpak", "run", "com.spotify.Client"]);
+
+        let telegram = suggest_from(&indexes, &unresolved("TelegramDesktop", &["/snap/telegram-desktop/6/bin/telegram-desktop"])).unwrap();
+        assert_eq!(telegram.package, Package::Snap);
+        assert_eq!(telegram.command, vec!["snap", "run", "telegram-desktop"]);
+
+        assert!(suggest_from(&indexes, &unresolved("mystery", &["/opt/mystery/bin/run"])).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::arguments::filter_arguments;
+use crate::command_detection::{command_exists_in_path, executable_name};
+use crate::config::Config;
+use crate::process;
+
+/// Programs that are safe to rerun inside a terminal without asking
+const SAFE_PROGRAMS: &[&str] = &[
+    "htop", "btop", "top", "nvtop", "nvim", "vim", "vi", "nano", "hx", "helix", "micro", "emacs",
+    "less", "man", "ssh", "mosh", "tmux", "zellij", "screen", "ranger", "yazi", "lf", "nnn", "mc",
+    "ncdu", "lazygit", "tig", "k9s", "cmus", "ncmpcpp", "weechat", "irssi", "newsboat", "aerc", "neomutt",
+];
+
+/// How a terminal emulator is told where to start and what to run
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TerminalProfile {
+    /// Arguments that open the terminal in a directory, `{}` is replaced by the path
+    pub directory: Vec<String>,
+    /// Arguments placed between the terminal's own arguments and the program it should run
+    #[serde(default)]
+    pub exec: Vec<String>,
+}
+
+/// Programs that may be rerun inside terminals, in addition to the built-in list
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct ProgramFilter {
+    pub allow: Vec<String>,
+    /// Programs that are never rerun, even if they are on the built-in list
+    pub deny: Vec<String>,
+}
+
+impl ProgramFilter {
+    pub fn is_allowed(&self, name: &str) -> bool {
+        !self.deny.iter().any(|program| program == name)
+            && (SAFE_PROGRAMS.contains(&name) || self.allow.iter().any(|program| program == name))
+    }
+}
+
+fn profile(directory: &[&str], exec: &[&str]) -> TerminalProfile {
+    TerminalProfile {
+        directory: directory.iter().map(|arg| arg.to_string()).collect(),
+        exec: exec.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+fn builtin_terminal(name: &str) -> Option<TerminalProfile> {
+    match name {
+        "kitty" => Some(profile(&["--directory", "{}"], &[])),
+        "foot" | "footclient" => Some(profile(&["--working-directory={}"], &[])),
+        "alacritty" => Some(profile(&["--working-directory", "{}"], &["-e"])),
+        "wezterm" | "wezterm-gui" => Some(profile(&["start", "--cwd", "{}"], &["--"])),
+        "ghostty" => Some(profile(&["--working-directory={}"], &["-e"])),
+        _ => None,
+    }
+}
+
+/// Terminals tried in order when a program needs a new terminal and `$TERMINAL` isn't set
+const DEFAULT_TERMINALS: &[&str] = &["xdg-terminal-exec", "kitty", "foot", "alacritty", "wezterm", "ghostty"];
+
+/// The command running a program in a new terminal
+pub fn in_terminal(config: &Config, program: &[String]) -> Vec<String> {
+    let terminal = std::env::var("TERMINAL")
+        .ok()
+        .filter(|terminal| !terminal.is_empty())
+        .or_else(|| DEFAULT_TERMINALS.iter().find(|name| command_exists_in_path(name)).map(|name| name.to_string()))
+        .unwrap_or_else(|| DEFAULT_TERMINALS[0].to_string());
+    run_in(config, terminal, program)
+}
+
+/// The command running a program in the given terminal
+fn run_in(config: &Config, terminal: String, program: &[String]) -> Vec<String> {
+    // Most terminals not known to Hyprsession take the program after -e
+    let exec = match terminal_profile(config, &terminal) {
+        Some(profile) => profile.exec,
+        None if executable_name(&terminal) == "xdg-terminal-exec" => vec![],
+        None => vec![String::from("-e")],
+    };
+
+    let mut parts = vec![terminal];
+    parts.extend(exec);
+    parts.extend(program.iter().cloned());
+    parts
+}
+
+/// The command opening another window of a terminal. The shells of windows after the first can't be told apart,
+/// so they open in the terminal's default directory.
+pub fn new_window_command(argv: &[String], config: &Config) -> Option<Vec<String>> {
+    let program = argv.first()?;
+    terminal_profile(config, program)?;
+    // A foot server opens no window itself, its windows come from footclient
+    if executable_name(program) == "foot" && argv.iter().any(|arg| arg == "--server" || arg == "-s") {
+        return Some(vec![String::from("footclient")]);
+    }
+    Some(argv.to_vec())
+}
+
+/// The profile of a terminal emulator, preferring ones given in the config
+pub fn terminal_profile(config: &Config, program: &str) -> Option<TerminalProfile> {
+    let name = executable_name(program);
+    config.terminals.get(name).cloned().or_else(|| builtin_terminal(name))
+}
+
+impl TerminalProfile {
+    /// Arguments opening the terminal in the given directory
+    pub fn directory_args(&self, dir: &Path) -> Vec<String> {
+        self.directory
+            .iter()
+            .map(|arg| arg.replace("{}", &dir.to_string_lossy()))
+            .collect()
+    }
+
+    /// Insert the directory arguments after the terminal, after its subcommand if the command already has it
+    fn add_directory(&self, parts: &mut Vec<String>, dir: &Path) {
+        let mut args = self.directory_args(dir);
+        let mut at = 1;
+        let subcommand = args.first().filter(|arg| !arg.starts_with('-'));
+        if subcommand.is_some() && parts.get(1) == subcommand {
+            args.remove(0);
+            at = 2;
+        }
+        parts.splice(at..at, args);
+    }
+}
+
+/// The shell most recently started by a terminal emulator
+fn terminal_shell(pid: i32) -> Option<process::ProcStat> {
+    process::children(pid)
+        .into_iter()
+        .filter(|child| child.tty_nr != 0)
+        .max_by_key(|child| child.start_time)
+}
+
+/// The job in the foreground of a shell, if the shell isn't waiting at its prompt
+fn foreground_job(shell: &process::ProcStat) -> Option<process::ProcStat> {
+    if shell.tpgid <= 0 || shell.tpgid == shell.pgrp {
+        return None;
+    }
+    process::stat(shell.tpgid)
+}
+
+/// The arguments needed to rerun a foreground program if it is allowed
+fn rerun_args(job: &process::ProcStat, filter: &ProgramFilter) -> Option<Vec<String>> {
+    // tmux replaces its arguments with its process title so only reattaching can be offered
+    if job.comm.starts_with("tmux") && filter.is_allowed("tmux") {
+        return Some(vec![String::from("tmux"), String::from("attach")]);
+    }
+
+    let args = process::cmdline(job.pid)?;
+    filter.is_allowed(executable_name(&args[0])).then_some(args)
+}
+
+/// Relaunch terminals in the working directory of the shell running inside them,
+/// running the program that was in the foreground if it is safe to do so
+pub fn restore_terminal(mut parts: Vec<String>, pid: i32, config: &Config) -> Vec<String> {
+    let Some(profile) = parts.first().and_then(|program| terminal_profile(config, program)) else {
+        return parts;
+    };
+    let Some(shell) = terminal_shell(pid) else {
+        return parts;
+    };
+
+    let program = foreground_job(&shell)
+        .and_then(|job| Some((rerun_args(&job, &config.terminal_programs)?, job.pid)));
+
+    // Relative paths given to the program are relative to its own directory
+    let dir = process::cwd(program.as_ref().map_or(shell.pid, |(_, pid)| *pid));
+    let is_home = |dir: &Path| std::env::var("HOME").is_ok_and(|home| Path::new(&home) == dir);
+    if let Some(dir) = dir.filter(|dir| !is_home(dir)) {
+        profile.add_directory(&mut parts, &dir);
+    }
+    // The program's arguments are filtered for the program itself like any detected command
+    if let Some((args, _)) = program {
+        parts.extend(profile.exec.iter().cloned());
+        parts.extend(filter_arguments(args, config.compiled_filters()));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_profile() {
+        let config: Config = serde_json::from_str(r#"{
+            "terminals": { "st": { "directory": ["-d", "{}"] } }
+        }"#).unwrap();
+
+        let st = terminal_profile(&config, "st").unwrap();
+        assert_eq!(st.directory_args(Path::new("/tmp/x")), vec!["-d", "/tmp/x"]);
+
+        let foot = terminal_profile(&config, "/usr/bin/foot").unwrap();
+        assert_eq!(foot.directory_args(Path::new("/src")), vec!["--working-directory=/src"]);
+
+        assert!(terminal_profile(&config, "firefox").is_none());
+    }
+
+    #[test]
+    fn test_add_directory() {
+        let wezterm = terminal_profile(&Config::default(), "wezterm").unwrap();
+        let add = |command: &[&str]| {
+            let mut parts: Vec<String> = command.iter().map(|arg| arg.to_string()).collect();
+            wezterm.add_directory(&mut parts, Path::new("/src"));
+            parts.join(" ")
+        };
+        assert_eq!(add(&["wezterm"]), "wezterm start --cwd /src");
+        assert_eq!(add(&["wezterm", "start", "--", "htop"]), "wezterm start --cwd /src -- htop");
+
+        let kitty = terminal_profile(&Config::default(), "kitty").unwrap();
+        let mut parts = vec![String::from("kitty"), String::from("--single-instance")];
+        kitty.add_directory(&mut parts, Path::new("/src"));
+        assert_eq!(parts, vec!["kitty", "--directory", "/src", "--single-instance"]);
+    }
+
+    #[test]
+    fn test_new_window_command() {
+        let config = Config::default();
+        let strings = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        let kitty = strings(&["kitty", "--single-instance"]);
+        assert_eq!(new_window_command(&kitty, &config).unwrap(), kitty);
+        assert_eq!(new_window_command(&strings(&["foot", "--server"]), &config).unwrap(), strings(&["footclient"]));
+        assert!(new_window_command(&strings(&["gimp"]), &config).is_none());
+    }
+
+    #[test]
+    fn test_in_terminal() {
+        let config = Config::default();
+        let run = |terminal: &str| run_in(&config, terminal.to_string(), &["htop".to_string()]).join(" ");
+        assert_eq!(run("alacritty"), "alacritty -e htop");
+        assert_eq!(run("kitty"), "kitty htop");
+        assert_eq!(run("xdg-terminal-exec"), "xdg-terminal-exec htop");
+        assert_eq!(run("urxvt"), "urxvt -e htop");
+    }
+
+    #[test]
+    fn test_program_filter() {
+        let filter: ProgramFilter = serde_json::from_str(r#"{ "allow": ["cargo"], "deny": ["ssh"] }"#).unwrap();
+        assert!(filter.is_allowed("htop"));
+        assert!(filter.is_allowed("cargo"));
+        assert!(!filter.is_allowed("ssh"));
+        assert!(!filter.is_allowed("rm"));
+    }
+}
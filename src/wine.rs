@@ -0,0 +1,172 @@
+use hyprland::data::Client;
+use std::collections::HashMap;
+use crate::command_detection::{executable_name, flatpak_app_id, flatpak_command};
+use crate::config::Config;
+use crate::desktop_entry::DesktopIndex;
+use crate::process;
+
+const STEAM_FLATPAK: &str = "com.valvesoftware.Steam";
+
+/// Executables Wine runs Windows programs with
+const WINE_LOADERS: &[&str] = &["wine", "wine64", "wine-preloader", "wine64-preloader"];
+
+fn is_steam_id(id: &str) -> bool {
+    !id.is_empty() && id != "0" && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The id Steam launched a game with, from the variables it sets for games and Proton.
+/// Game ids of non-Steam shortcuts are preferred as their app id is 0.
+fn steam_game_id(environ: &HashMap<String, String>) -> Option<String> {
+    let compat_data_id = environ
+        .get("STEAM_COMPAT_DATA_PATH")
+        .and_then(|path| path.trim_end_matches('/').rsplit('/').next());
+    ["SteamGameId", "SteamAppId", "STEAM_COMPAT_APP_ID"]
+        .iter()
+        .filter_map(|name| environ.get(*name).map(String::as_str))
+        .chain(compat_data_id)
+        .find(|id| is_steam_id(id))
+        .map(String::from)
+}
+
+/// The app id in the command Steam wraps every game in, e.g. `reaper SteamLaunch AppId=620 -- ...`
+fn reaper_app_id(args: &[String]) -> Option<String> {
+    if args.first().map(|program| executable_name(program)) != Some("reaper") {
+        return None;
+    }
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .find_map(|arg| arg.strip_prefix("AppId="))
+        .filter(|id| is_steam_id(id))
+        .map(String::from)
+}
+
+/// Whether a process with Steam's variables is the game rather than a program that inherited them,
+/// such as a terminal opened from a game: it was started by reaper, runs from the game's install or
+/// compatdata directory, or is run by Wine
+fn is_game_process(exe: &str, parent_args: &[String], environ: &HashMap<String, String>) -> bool {
+    let game_dirs = ["STEAM_COMPAT_INSTALL_PATH", "STEAM_COMPAT_DATA_PATH"]
+        .iter()
+        .filter_map(|name| environ.get(*name))
+        .filter(|dir| !dir.is_empty());
+    parent_args.first().is_some_and(|program| executable_name(program) == "reaper")
+        || WINE_LOADERS.contains(&executable_name(exe))
+        || exe.contains("/steamapps/common/")
+        || game_dirs.into_iter().any(|dir| exe.starts_with(dir.as_str()))
+}
+
+/// The Steam game a process belongs to, looking at its ancestors for games that clear their environment
+fn steam_game(pid: i32) -> Option<String> {
+    let mut current = pid;
+    loop {
+        let environ = process::environ(current);
+        if let Some(id) = steam_game_id(&environ) {
+            let exe = process::exe(current).map(|exe| exe.to_string_lossy().into_owned()).unwrap_or_default();
+            let parent_args = process::stat(current).and_then(|stat| process::cmdline(stat.ppid)).unwrap_or_default();
+            return is_game_process(&exe, &parent_args, &environ).then_some(id);
+        }
+        if let Some(id) = process::cmdline(current).and_then(|args| reaper_app_id(&args)) {
+            return Some(id);
+        }
+        current = process::stat(current).map(|stat| stat.ppid).filter(|ppid| *ppid > 1)?;
+    }
+}
+
+fn steam_command(game_id: &str, flatpak: bool) -> Vec<String> {
+    let url = format!("steam://rungameid/{}", game_id);
+    match flatpak {
+        true => flatpak_command(STEAM_FLATPAK, &[url]),
+        false => vec![String::from("steam"), url],
+    }
+}
+
+/// Games started by Steam, natively or through Proton, are relaunched through Steam
+pub fn handle_steam(client: &Client) -> Result<Vec<String>, std::io::Error> {
+    let game_id = steam_game(client.pid)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Not a Steam game"))?;
+    let flatpak = flatpak_app_id(client.pid).is_some_and(|app_id| app_id == STEAM_FLATPAK);
+    Ok(steam_command(&game_id, flatpak))
+}
+
+/// Whether a process runs a Windows program, which Wine shows as the program's path in place of the loader
+fn is_wine(args: &[String], exe: &str) -> bool {
+    WINE_LOADERS.contains(&executable_name(exe))
+        || args.first().is_some_and(|program| program.to_lowercase().ends_with(".exe"))
+}
+
+/// Command running a Windows program in a Wine prefix
+fn wine_command(args: &[String], prefix: Option<&str>) -> Vec<String> {
+    let mut command = vec![];
+    if let Some(prefix) = prefix {
+        command.extend([String::from("env"), format!("WINEPREFIX={}", prefix)]);
+    }
+    command.push(String::from("wine"));
+    command.extend(args.iter().cloned());
+    command
+}
+
+/// Windows programs are restored from the desktop entry Wine created for them, or run again in their prefix
+pub fn handle_wine(client: &Client, desktop: &DesktopIndex, config: &Config) -> Result<Vec<String>, std::io::Error> {
+    let args = process::cmdline(client.pid).unwrap_or_default();
+    let exe = process::exe(client.pid).map(|exe| exe.to_string_lossy().into_owned()).unwrap_or_default();
+    if !is_wine(&args, &exe) {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Not a Wine program"));
+    }
+
+    // Wine names its desktop entries' window class after the program, e.g. `notepad++.exe`
+    let program = args.first().map(|program| program.rsplit(['\\', '/']).next().unwrap_or(program).to_lowercase());
+    if let Some(entry) = desktop.find(&client.initial_class).or_else(|| program.and_then(|program| desktop.find(&program))) {
+        return Ok(entry.command(config));
+    }
+    if args.first().is_none_or(|program| WINE_LOADERS.contains(&executable_name(program))) {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Windows program not found"));
+    }
+    Ok(wine_command(&args, process::environ(client.pid).get("WINEPREFIX").map(String::as_str)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_steam_game_id() {
+        let environ = |vars: &[(&str, &str)]| -> HashMap<String, String> {
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        assert_eq!(steam_game_id(&environ(&[("SteamAppId", "620"), ("SteamGameId", "620")])).unwrap(), "620");
+        assert_eq!(steam_game_id(&environ(&[("SteamAppId", "0"), ("SteamGameId", "12950238946426912768")])).unwrap(), "12950238946426912768");
+        let compat = environ(&[("STEAM_COMPAT_DATA_PATH", "/home/user/.steam/steam/steamapps/compatdata/1091500/")]);
+        assert_eq!(steam_game_id(&compat).unwrap(), "1091500");
+        assert!(steam_game_id(&environ(&[("SteamAppId", "0")])).is_none());
+
+        let reaper = strings(&["/home/user/.steam/ubuntu12_32/reaper", "SteamLaunch", "AppId=620", "--", "portal2"]);
+        assert_eq!(reaper_app_id(&reaper).unwrap(), "620");
+        assert!(reaper_app_id(&strings(&["portal2", "AppId=620"])).is_none());
+
+        let game = environ(&[("SteamAppId", "620"), ("STEAM_COMPAT_DATA_PATH", "/home/user/.steam/steam/steamapps/compatdata/620")]);
+        assert!(is_game_process("/usr/bin/bash", &reaper, &game));
+        assert!(is_game_process("/home/user/.steam/steam/steamapps/common/Portal 2/portal2_linux", &[], &game));
+        assert!(is_game_process("/mnt/games/Proton 9.0/files/bin/wine64-preloader", &[], &game));
+        assert!(!is_game_process("/usr/bin/kitty", &strings(&["portal2_linux"]), &game));
+
+        assert_eq!(steam_command("620", false), strings(&["steam", "steam://rungameid/620"]));
+        assert_eq!(steam_command("620", true), strings(&["flatpak", "run", STEAM_FLATPAK, "steam://rungameid/620"]));
+    }
+
+    #[test]
+    fn test_wine_command() {
+        let args = strings(&["C:\\Program Files\\Notepad++\\notepad++.exe", "readme.txt"]);
+        assert!(is_wine(&args, "/usr/bin/wine64-preloader"));
+        assert!(is_wine(&args, ""));
+        assert!(!is_wine(&strings(&["/usr/bin/kitty"]), "/usr/bin/kitty"));
+
+        assert_eq!(
+            wine_command(&args, Some("/home/user/.wine-apps")),
+            strings(&["env", "WINEPREFIX=/home/user/.wine-apps", "wine", "C:\\Program Files\\Notepad++\\notepad++.exe", "readme.txt"])
+        );
+        assert_eq!(wine_command(&args[..1], None), strings(&["wine", "C:\\Program Files\\Notepad++\\notepad++.exe"]));
+    }
+}
@@ -20,7 +20,7 @@ mod documentation_tests {
             .nth(1)
             .expect("Could not extract version from Cargo.toml")
             .split('-')
-            .nth(0)
+            .next()
             .expect("Could not extract version from Cargo.toml")
             .trim();
         
@@ -44,7 +44,7 @@ mod documentation_tests {
             .expect("Could not find '## Change log' section in README.md");
         
         let version_position = readme.find(&version_section)
-            .expect(&format!("Version section {} not found in README.md", version_section));
+            .unwrap_or_else(|| panic!("Version section {} not found in README.md", version_section));
         
         assert!(
             version_position > changelog_start,
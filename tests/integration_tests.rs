@@ -16,6 +16,7 @@ fn test_load_session_with_nonexistent_file() {
         load_time: 1, // Reduced load time for faster tests
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // This should not panic or fail when the session file doesn't exist
@@ -38,6 +39,7 @@ fn test_load_session_with_empty_file() {
         load_time: 1, // Reduced load time for faster tests
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // This should handle empty files gracefully
@@ -70,6 +72,7 @@ fn test_load_session_with_sample_data() {
         load_time: 1, // Reduced from 30 to 1 for faster tests
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Load the session in simulate mode (won't actually execute commands)
@@ -94,6 +97,7 @@ fn test_load_session_with_malformed_data() {
         load_time: 1, // Reduced from 30 to 1 for faster tests
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
     
     // This should handle malformed data gracefully
@@ -114,6 +118,7 @@ fn test_session_trait_save_method() {
         load_time: 1,
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Test saving a session (should work even in simulate mode for basic functionality)
@@ -134,6 +139,7 @@ fn test_session_trait_list_method() {
         load_time: 1,
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Create some mock session directories
@@ -163,6 +169,7 @@ fn test_session_trait_delete_method() {
         load_time: 1,
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Create a mock session directory
@@ -191,6 +198,7 @@ fn test_session_trait_clear_method() {
         load_time: 1,
         adjust_clients_only: false,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Test clearing session (should work in simulate mode)
@@ -216,6 +224,7 @@ fn test_local_session_struct_creation() {
         load_time: 60,
         adjust_clients_only: false,
         save_duplicate_pids: true,
+        ..Default::default()
     };
 
     // Test simulation configuration
@@ -225,16 +234,17 @@ fn test_local_session_struct_creation() {
         load_time: 5,
         adjust_clients_only: true,
         save_duplicate_pids: false,
+        ..Default::default()
     };
 
     // Verify fields are set correctly
-    assert_eq!(session1.simulate, false);
+    assert!(!session1.simulate);
     assert_eq!(session1.load_time, 60);
-    assert_eq!(session1.save_duplicate_pids, true);
+    assert!(session1.save_duplicate_pids);
     
-    assert_eq!(session2.simulate, true);
+    assert!(session2.simulate);
     assert_eq!(session2.load_time, 5);
-    assert_eq!(session2.adjust_clients_only, true);
+    assert!(session2.adjust_clients_only);
 }
 
 
@@ -246,7 +256,7 @@ mod cli_tests {
     #[test]
     fn test_cli_help() {
         let output = Command::new("cargo")
-            .args(&["run", "--", "--help"])
+            .args(["run", "--", "--help"])
             .output()
             .expect("Failed to execute command");
 
@@ -260,7 +270,7 @@ mod cli_tests {
     #[test]
     fn test_cli_version() {
         let output = Command::new("cargo")
-            .args(&["run", "--", "--version"])
+            .args(["run", "--", "--version"])
             .output()
             .expect("Failed to execute command");
 
@@ -272,7 +282,7 @@ mod cli_tests {
     #[test]
     fn test_cli_simulate_mode() {
         let output = Command::new("cargo")
-            .args(&["run", "--", "save-and-exit", "--simulate"])
+            .args(["run", "--", "save-and-exit", "--simulate"])
             .output()
             .expect("Failed to execute command");
 
@@ -291,7 +301,7 @@ mod cli_tests {
         let session_path = temp_dir.path().to_str().unwrap();
 
         let output = Command::new("cargo")
-            .args(&["run", "--", "list"])
+            .args(["run", "--", "list"])
             .env("HYPRSESSION_PATH", session_path)
             .output()
             .expect("Failed to execute command");
@@ -311,7 +321,7 @@ mod cli_tests {
         std::fs::create_dir(format!("{}/session2", session_path)).unwrap();
 
         let output = Command::new("cargo")
-            .args(&["run", "--", "list"])
+            .args(["run", "--", "list"])
             .env("HYPRSESSION_PATH", session_path)
             .output()
             .expect("Failed to execute command");
@@ -330,7 +340,7 @@ mod cli_tests {
     #[ignore]
     fn test_cli_invalid_save_interval() {
         let output = Command::new("cargo")
-            .args(&["run", "--", "--save-interval", "0"])
+            .args(["run", "--", "--save-interval", "0"])
             .output()
             .expect("Failed to execute command");
 